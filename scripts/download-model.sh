@@ -3,7 +3,8 @@
 # Download a small, capable model for Otto
 # Using Qwen2.5-0.5B-Instruct - small but good at following instructions
 
-MODEL_DIR="src-tauri/models"
+# Into src-tauri/models, which is bundled with the app at build time
+MODEL_DIR="$(cd "$(dirname "$0")/.." && pwd)/src-tauri/models"
 MODEL_FILE="model.gguf"
 MODEL_URL="https://huggingface.co/Qwen/Qwen2.5-0.5B-Instruct-GGUF/resolve/main/qwen2.5-0.5b-instruct-q4_k_m.gguf"
 
@@ -19,6 +20,8 @@ if [ $? -eq 0 ]; then
     echo ""
     echo "Download complete!"
     echo "Model saved to: $MODEL_DIR/$MODEL_FILE"
+    echo "It is bundled with the next build. Otto uses it when Ollama isn't running,"
+    echo "or always with \"backend\": \"embedded\"."
 else
     echo ""
     echo "Download failed. Please try again or download manually from:"
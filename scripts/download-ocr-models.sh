@@ -0,0 +1,28 @@
+#!/bin/bash
+
+# Download the text detection and recognition models used for OCR (ocrs)
+
+# Into src-tauri/models/ocr, which is bundled with the app at build time
+MODEL_DIR="$(cd "$(dirname "$0")/.." && pwd)/src-tauri/models/ocr"
+MODEL_BASE_URL="https://ocrs-models.s3-accelerate.amazonaws.com"
+MODEL_FILES="text-detection.rten text-recognition.rten"
+
+mkdir -p "$MODEL_DIR"
+
+echo "Downloading ocrs text detection and recognition models..."
+echo "Size: ~12MB"
+echo ""
+
+for file in $MODEL_FILES; do
+    curl -L -o "$MODEL_DIR/$file" "$MODEL_BASE_URL/$file" --progress-bar
+    if [ $? -ne 0 ]; then
+        echo ""
+        echo "Download failed. Please try again or download manually from:"
+        echo "$MODEL_BASE_URL/$file"
+        exit 1
+    fi
+done
+
+echo ""
+echo "Download complete!"
+echo "Models saved to: $MODEL_DIR"
@@ -1,8 +1,24 @@
-use crate::llm;
+use crate::llm::{self, LlmError};
 use crate::types::{Goal, DecompositionInfo, LlmCallType};
 use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
 use tauri::AppHandle;
 
+/// Goal list as the LLM returns it
+#[derive(Deserialize, JsonSchema)]
+struct LlmGoalList {
+    goals: Vec<LlmGoal>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LlmGoal {
+    /// What to do
+    description: String,
+    /// How to know it worked
+    success_criteria: String,
+}
+
 /// Result of decomposition including method info
 pub struct DecomposeResult {
     pub goals: Vec<Goal>,
@@ -123,26 +139,78 @@ fn build_search_goals(app: &str, query: &str) -> Vec<Goal> {
 /// Use LLM to decompose complex commands
 async fn decompose_with_llm(app_handle: &AppHandle, command: &str) -> Result<Vec<Goal>, String> {
     let prompt = super::prompts::decomposition_prompt(command);
-    let response = llm::call_ollama_with_debug(app_handle, &prompt, LlmCallType::Decomposition).await?;
+    let response = llm::call_with_schema(
+        app_handle,
+        &prompt,
+        LlmCallType::Decomposition,
+        llm::schema_for::<LlmGoalList>(),
+    )
+    .await?;
     println!("[DECOMPOSER] LLM response:\n{}", response);
-    parse_goals_from_response(&response)
+    Ok(parse_goals_from_response(&response)?)
 }
 
-/// Parse goals from LLM response
-fn parse_goals_from_response(response: &str) -> Result<Vec<Goal>, String> {
+/// Parse goals from the LLM response: schema JSON, or the numbered lines
+/// models that ignore `format` still write
+fn parse_goals_from_response(response: &str) -> Result<Vec<Goal>, LlmError> {
+    let goals = match parse_goal_list(response) {
+        Ok(goals) => goals,
+        Err(e) => {
+            let goals = parse_goal_lines(response);
+            if goals.is_empty() {
+                return Err(e);
+            }
+            println!("[DECOMPOSER] Response is not goal JSON ({}), read it as numbered lines", e);
+            goals
+        }
+    };
+
+    for (i, goal) in goals.iter().enumerate() {
+        println!("[DECOMPOSER]   Goal {}: \"{}\"", i + 1, goal.description);
+    }
+
+    Ok(goals)
+}
+
+/// Goals from a schema-constrained response
+fn parse_goal_list(response: &str) -> Result<Vec<Goal>, LlmError> {
+    let list: LlmGoalList = llm::parse_structured(response)?;
+
+    let goals: Vec<Goal> = list
+        .goals
+        .into_iter()
+        .filter(|g| !g.description.trim().is_empty())
+        .map(|g| {
+            let description = g.description.trim().to_string();
+            let success_criteria = match g.success_criteria.trim() {
+                "" => format!("{} is completed", description),
+                criteria => criteria.to_string(),
+            };
+            Goal::new(description, success_criteria)
+        })
+        .collect();
+
+    if goals.is_empty() {
+        return Err(LlmError::SchemaViolation {
+            target: "LlmGoalList",
+            message: "no goals in response".into(),
+        });
+    }
+    Ok(goals)
+}
+
+/// Goals from "N. description | success_criteria" lines
+fn parse_goal_lines(response: &str) -> Vec<Goal> {
     let mut goals = Vec::new();
 
     // Primary pattern: "N. description | success_criteria"
-    let pipe_pattern = Regex::new(r"^\d+\.\s*(.+?)\s*\|\s*(.+)$")
-        .map_err(|e| format!("Regex error: {}", e))?;
+    let pipe_pattern = Regex::new(r"^\d+\.\s*(.+?)\s*\|\s*(.+)$").unwrap();
 
     // Fallback pattern: "N. description - success_criteria" (common LLM mistake)
-    let dash_pattern = Regex::new(r"^\d+\.\s*(.+?)\s+-\s+(.+)$")
-        .map_err(|e| format!("Regex error: {}", e))?;
+    let dash_pattern = Regex::new(r"^\d+\.\s*(.+?)\s+-\s+(.+)$").unwrap();
 
     // Simple pattern: "N. description" (generate default success criteria)
-    let simple_pattern = Regex::new(r"^\d+\.\s*(.+)$")
-        .map_err(|e| format!("Regex error: {}", e))?;
+    let simple_pattern = Regex::new(r"^\d+\.\s*(.+)$").unwrap();
 
     for line in response.lines() {
         let line = line.trim();
@@ -150,22 +218,9 @@ fn parse_goals_from_response(response: &str) -> Result<Vec<Goal>, String> {
             continue;
         }
 
-        // Try pipe separator first (preferred)
-        if let Some(caps) = pipe_pattern.captures(line) {
-            let description = caps.get(1).map(|m| m.as_str().trim()).unwrap_or("");
-            let success_criteria = caps.get(2).map(|m| m.as_str().trim()).unwrap_or("");
-
-            if !description.is_empty() && !success_criteria.is_empty() {
-                goals.push(Goal::new(
-                    description.to_string(),
-                    success_criteria.to_string(),
-                ));
-                continue;
-            }
-        }
-
-        // Try dash separator (fallback)
-        if let Some(caps) = dash_pattern.captures(line) {
+        // Try pipe separator first (preferred), then dash separator (fallback)
+        let separated = pipe_pattern.captures(line).or_else(|| dash_pattern.captures(line));
+        if let Some(caps) = separated {
             let description = caps.get(1).map(|m| m.as_str().trim()).unwrap_or("");
             let success_criteria = caps.get(2).map(|m| m.as_str().trim()).unwrap_or("");
 
@@ -195,18 +250,7 @@ fn parse_goals_from_response(response: &str) -> Result<Vec<Goal>, String> {
         }
     }
 
-    if goals.is_empty() {
-        return Err(format!(
-            "Could not parse goals from LLM response: {}",
-            response
-        ));
-    }
-
-    for (i, goal) in goals.iter().enumerate() {
-        println!("[DECOMPOSER]   Goal {}: \"{}\"", i + 1, goal.description);
-    }
-
-    Ok(goals)
+    goals
 }
 
 fn capitalize(s: &str) -> String {
@@ -223,22 +267,40 @@ mod tests {
 
     #[test]
     fn test_open_pattern() {
-        let goals = try_pattern_match("open safari").unwrap();
+        let (goals, _) = try_pattern_match("open safari").unwrap();
         assert_eq!(goals.len(), 1);
         assert!(goals[0].description.contains("Safari"));
     }
 
     #[test]
     fn test_search_pattern() {
-        let goals = try_pattern_match("open chrome and search for rust").unwrap();
+        let (goals, _) = try_pattern_match("open chrome and search for rust").unwrap();
         assert_eq!(goals.len(), 4);
         assert!(goals[0].description.contains("Chrome"));
     }
 
     #[test]
     fn test_click_pattern() {
-        let goals = try_pattern_match("click on the submit button").unwrap();
+        let (goals, _) = try_pattern_match("click on the submit button").unwrap();
         assert_eq!(goals.len(), 1);
         assert!(goals[0].description.contains("submit button"));
     }
+
+    #[test]
+    fn test_parse_goals_from_response() {
+        let goals = parse_goals_from_response(
+            r#"{"goals": [{"description": "Open Slack", "success_criteria": "Slack window is visible"}, {"description": "Find John's messages", "success_criteria": ""}]}"#,
+        )
+        .unwrap();
+        assert_eq!(goals.len(), 2);
+        assert_eq!(goals[0].success_criteria, "Slack window is visible");
+        assert_eq!(goals[1].success_criteria, "Find John's messages is completed");
+
+        // Models that ignore the schema still get their numbered lines read
+        let goals = parse_goals_from_response("1. Open Slack | Slack is visible\n2. Find John - Chat is open\n3. Reply").unwrap();
+        assert_eq!(goals.len(), 3);
+        assert_eq!(goals[1].success_criteria, "Chat is open");
+        assert_eq!(goals[2].success_criteria, "Reply is completed");
+        assert!(parse_goals_from_response("Sure, I can help with that.").is_err());
+    }
 }
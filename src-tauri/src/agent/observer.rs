@@ -1,49 +1,76 @@
-use crate::screenshot;
-use crate::types::{DetectedElement, ScreenState, UIElement};
-use crate::vision;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use crate::types::{DetectedElement, ScreenState, Screenshot, UIElement};
+use crate::vision::{self, VisionBackend};
+use crate::vision::diff::ScreenDiff;
 use std::process::Command;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::AppHandle;
+use tauri::{AppHandle, Runtime};
 
-pub struct Observer;
+pub struct Observer {
+    vision: Arc<dyn VisionBackend>,
+}
 
 impl Observer {
     pub fn new() -> Self {
-        Self
+        Self::with_vision(vision::backend())
+    }
+
+    /// An observer that looks at the screen through `vision`
+    pub fn with_vision(vision: Arc<dyn VisionBackend>) -> Self {
+        Self { vision }
     }
 
     /// Observe the current screen state - detect UI elements
-    pub async fn observe(&self, app_handle: &AppHandle, goal_context: &str) -> Result<ScreenState, String> {
+    pub async fn observe<R: Runtime>(&self, app_handle: &AppHandle<R>, goal_context: &str) -> Result<ScreenState, String> {
         println!("[OBSERVER] Capturing screenshot...");
         // Capture and resize screenshot
-        let (screenshot_bytes, scale_x, scale_y) = screenshot::capture_and_resize()
+        let (screenshot_bytes, scale_x, scale_y) = self.vision.capture()
             .map_err(|e| {
                 println!("[OBSERVER] Screenshot capture FAILED: {}", e);
                 e
             })?;
         println!("[OBSERVER] Screenshot captured: {} bytes", screenshot_bytes.len());
-        let screenshot_hash = hash_bytes(&screenshot_bytes);
+        let screenshot_hash = vision::diff::perceptual_hash(&screenshot_bytes)
+            .map(|hash| format!("{:016x}", hash))
+            .unwrap_or_default();
+
+        // Read text on the CPU while the vision model looks at the screenshot
+        let ocr = vision::ocr::is_available().then(|| {
+            let image = screenshot_bytes.clone();
+            tokio::task::spawn_blocking(move || vision::ocr::detect_text(&image))
+        });
 
         println!("[OBSERVER] Calling vision model to detect UI elements...");
-        // Detect UI elements from screenshot
-        let vision_elements = vision::detect_ui_elements(app_handle, &screenshot_bytes, goal_context).await
-            .map_err(|e| {
-                println!("[OBSERVER] Vision model FAILED: {}", e);
-                e
-            })?;
+        // Detect UI elements, scaled back to original screen size
+        let mut vision_elements =
+            vision::detect_screen_elements(app_handle, &self.vision, &screenshot_bytes, (scale_x, scale_y), goal_context)
+                .await
+                .map_err(|e| {
+                    println!("[OBSERVER] Vision model FAILED: {}", e);
+                    e
+                })?;
+
+        if let Some(ocr) = ocr {
+            match ocr.await {
+                Ok(Ok(text)) => {
+                    let text = text.into_iter().map(|e| e.scaled(scale_x, scale_y)).collect();
+                    vision_elements = vision::ocr::merge_elements(vision_elements, text);
+                }
+                Ok(Err(e)) => println!("[OBSERVER] OCR skipped: {}", e),
+                Err(e) => println!("[OBSERVER] OCR task failed: {}", e),
+            }
+        }
 
-        // Scale coordinates back to original screen size
         let ui_elements: Vec<UIElement> = vision_elements
             .into_iter()
             .map(|e| UIElement {
                 label: e.label,
                 element_type: e.element_type,
-                x1: (e.x1 as f64 * scale_x) as i32,
-                y1: (e.y1 as f64 * scale_y) as i32,
-                x2: (e.x2 as f64 * scale_x) as i32,
-                y2: (e.y2 as f64 * scale_y) as i32,
+                x1: e.x1,
+                y1: e.y1,
+                x2: e.x2,
+                y2: e.y2,
+                confidence: e.confidence,
             })
             .collect();
 
@@ -71,23 +98,49 @@ impl Observer {
             detected_elements: vec![],
             active_app,
             screenshot_hash,
+            screenshot: Some(Arc::new(Screenshot {
+                png: screenshot_bytes,
+                scale_x,
+                scale_y,
+            })),
         })
     }
 
+    /// The screen as it is now, without detecting elements: for comparing
+    /// before and after an action
+    pub fn capture(&self) -> Result<ScreenState, String> {
+        let (png, scale_x, scale_y) = self.vision.capture()?;
+        let mut state = ScreenState::new("Not analyzed".to_string());
+        state.screenshot_hash = vision::diff::perceptual_hash(&png)
+            .map(|hash| format!("{:016x}", hash))
+            .unwrap_or_default();
+        state.active_app = get_frontmost_app();
+        state.screenshot = Some(Arc::new(Screenshot { png, scale_x, scale_y }));
+        Ok(state)
+    }
+
     /// Find a specific element on screen
     pub async fn find_element(&self, element_description: &str) -> Result<DetectedElement, String> {
-        let screen_element = vision::find_element(element_description).await?;
+        let screen_element = vision::locate_element(self.vision.as_ref(), element_description).await?;
 
         Ok(DetectedElement {
             description: screen_element.description,
             location: Some((screen_element.x, screen_element.y)),
-            confidence: 1.0, // moondream doesn't provide confidence scores
+            confidence: screen_element.confidence,
         })
     }
 
-    /// Quick check if screen has changed (by hash comparison)
-    pub fn screen_changed(&self, old_hash: &str, new_hash: &str) -> bool {
-        old_hash != new_hash
+    /// How much the screen changed between two observations, with the
+    /// changed regions in screen points
+    pub async fn screen_changed(&self, before: &ScreenState, after: &ScreenState) -> Result<ScreenDiff, String> {
+        let (Some(before), Some(after)) = (before.screenshot.clone(), after.screenshot.clone()) else {
+            return Err("Observation has no screenshot".to_string());
+        };
+        let scale = (before.scale_x, before.scale_y);
+        let diff = tokio::task::spawn_blocking(move || vision::diff::diff(&before.png, &after.png))
+            .await
+            .map_err(|e| format!("Screen diff failed: {}", e))??;
+        Ok(diff.scaled(scale.0, scale.1))
     }
 }
 
@@ -108,15 +161,27 @@ fn get_frontmost_app() -> Option<String> {
     None
 }
 
-/// Hash bytes for quick comparison
-fn hash_bytes(bytes: &[u8]) -> String {
-    let mut hasher = DefaultHasher::new();
-    bytes.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
-}
-
 impl Default for Observer {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::replay;
+
+    #[tokio::test]
+    async fn test_observe_recorded_screen() {
+        let app = tauri::test::mock_app();
+
+        let observer = Observer::with_vision(Arc::new(replay("safari")));
+        let state = observer.observe(app.handle(), "Open apple.com").await.unwrap();
+        assert_eq!(state.ui_elements.len(), 3);
+        // Scaled from screenshot pixels to screen points
+        let address_bar = &state.ui_elements[0];
+        assert_eq!(address_bar.label, "Address bar");
+        assert_eq!((address_bar.x1, address_bar.y1, address_bar.x2, address_bar.y2), (880, 80, 1680, 128));
+    }
+}
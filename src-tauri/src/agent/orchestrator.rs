@@ -1,9 +1,12 @@
+use crate::cancel;
 use crate::computer;
+use crate::llm::{repair, usage};
+use crate::vision;
 use crate::types::{
     ActionParams, ActionResult, AgentSession, AgentState, AtomicAction,
-    DecompositionInfo, GoalStatus, MouseButton, ScreenState,
+    DecompositionInfo, Goal, GoalStatus, MouseButton, ScreenState,
 };
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::vision::diff::ScreenDiff;
 use tauri::{AppHandle, Emitter};
 use tokio::time::{sleep, Duration};
 
@@ -12,20 +15,6 @@ use super::observer::Observer;
 use super::thinker::Thinker;
 use super::verifier::Verifier;
 
-static CANCELLED: AtomicBool = AtomicBool::new(false);
-
-pub fn cancel_agent() {
-    CANCELLED.store(true, Ordering::SeqCst);
-}
-
-fn reset_cancellation() {
-    CANCELLED.store(false, Ordering::SeqCst);
-}
-
-fn is_cancelled() -> bool {
-    CANCELLED.load(Ordering::SeqCst)
-}
-
 pub struct AgentOrchestrator {
     session: AgentSession,
     observer: Observer,
@@ -47,7 +36,8 @@ impl AgentOrchestrator {
 
     /// Run the agent loop
     pub async fn run(&mut self) -> Result<(), String> {
-        reset_cancellation();
+        cancel::reset();
+        usage::reset();
 
         println!("\n========================================");
         println!("[AGENT] Starting agent session");
@@ -73,6 +63,7 @@ impl AgentOrchestrator {
                 self.emit_decomposition(&result.info);
                 self.emit_goals_ready();
             }
+            Err(_) if cancel::is_cancelled() => return self.end_cancelled(),
             Err(e) => {
                 println!("[PHASE 1] ERROR: Decomposition failed: {}", e);
                 self.set_error(&e);
@@ -83,10 +74,8 @@ impl AgentOrchestrator {
         // Phase 2: Process each goal
         println!("\n[PHASE 2] Processing goals...");
         while self.session.current_goal_index < self.session.goals.len() {
-            if is_cancelled() {
-                println!("[PHASE 2] Cancelled by user");
-                self.set_error("Cancelled by user");
-                return Err("Cancelled".to_string());
+            if cancel::is_cancelled() {
+                return self.end_cancelled();
             }
 
             if self.session.total_actions >= self.session.max_total_actions {
@@ -109,6 +98,7 @@ impl AgentOrchestrator {
                     // Move to next goal
                     self.session.current_goal_index += 1;
                 }
+                Err(_) if cancel::is_cancelled() => return self.end_cancelled(),
                 Err(e) => {
                     println!("[GOAL {}/{}] Failed: {}", goal_num, total_goals, e);
                     self.set_error(&e);
@@ -121,6 +111,8 @@ impl AgentOrchestrator {
         println!("\n========================================");
         println!("[AGENT] All goals completed successfully!");
         println!("========================================\n");
+        usage::log_summary();
+        repair::log_summary();
         self.update_state(AgentState::Complete);
         self.emit_session_complete();
 
@@ -153,12 +145,12 @@ impl AgentOrchestrator {
     /// Process a goal using Think-first flow:
     /// 1. Think (blind) → decide action without seeing screen
     /// 2. If action needs coordinates → Observe → Think again
-    /// 3. Execute
-    /// 4. Verify if needed
+    /// 3. Execute, capturing the screen before and after
+    /// 4. Verify if needed; if not achieved, loop with what the screen shows
     async fn process_goal_think_first(&mut self, goal_index: usize) -> Result<(), String> {
         loop {
-            if is_cancelled() {
-                return Err("Cancelled".to_string());
+            if cancel::is_cancelled() {
+                return Err(cancel::CANCELLED_MESSAGE.to_string());
             }
 
             let goal = self.session.goals.get(goal_index).cloned();
@@ -210,6 +202,7 @@ impl AgentOrchestrator {
                             g.attempts += 1;
                         }
                         println!("[STEP 2] ERROR: Observation failed: {}", e);
+                        self.thinker.record_error(&format!("could not see the screen: {}", e));
                         continue;
                     }
                 };
@@ -229,6 +222,7 @@ impl AgentOrchestrator {
                             g.attempts += 1;
                         }
                         println!("[STEP 2b] ERROR: Could not find element: {}", e);
+                        self.thinker.record_error(&format!("could not find '{}' on screen", element));
                         continue;
                     }
                 }
@@ -243,9 +237,26 @@ impl AgentOrchestrator {
             // Step 3: Execute
             println!("[STEP 3] Executing action: {:?}", final_action.action_type);
             self.update_state(AgentState::Acting);
-            let result = execute_atomic(&final_action).await;
+            let before = self
+                .observer
+                .capture()
+                .inspect_err(|e| println!("[STEP 3] Could not capture the screen before acting: {}", e))
+                .ok();
+            let outcome = execute_atomic(&final_action).await;
+            let change = match &before {
+                Some(before) => self.measure_change(before).await,
+                None => None,
+            };
+            let result = ActionResult {
+                action_id: final_action.id.clone(),
+                success: outcome.is_ok(),
+                error_message: outcome.err(),
+                // Unmeasured changes are assumed, as an action usually changes something
+                screen_changed: change.as_ref().is_none_or(|(_, diff)| diff.changed()),
+            };
             println!("[STEP 3] Result: {}", if result.success { "SUCCESS" } else { "FAILED" });
 
+            self.thinker.record_result(&result);
             self.session.action_history.push(result.clone());
             self.session.total_actions += 1;
             self.emit_action_completed(&result);
@@ -264,9 +275,15 @@ impl AgentOrchestrator {
             } else {
                 println!("[STEP 4] Verifying goal completion...");
                 self.update_state(AgentState::Verifying);
-                // For click actions, we assume success if the click executed
-                // More sophisticated verification can be added later
-                true
+                match &change {
+                    Some((after, diff)) => self.verify_goal(&goal, &final_action, after, diff).await,
+                    None => {
+                        // Unverified is not achieved: count the attempt and try again
+                        println!("[STEP 4] No captures to verify with -> NOT_ACHIEVED");
+                        self.thinker.record_error("the screen could not be captured to check the goal");
+                        false
+                    }
+                }
             };
 
             if goal_achieved {
@@ -288,6 +305,63 @@ impl AgentOrchestrator {
         Ok(())
     }
 
+    /// The screen once it settled after an action, and how it differs from
+    /// `before`. None when it can't be captured or compared.
+    async fn measure_change(&self, before: &ScreenState) -> Option<(ScreenState, ScreenDiff)> {
+        sleep(SETTLE_DELAY).await;
+        let after = match self.observer.capture() {
+            Ok(after) => after,
+            Err(e) => {
+                println!("[STEP 3] Could not capture the screen after acting: {}", e);
+                return None;
+            }
+        };
+
+        match self.observer.screen_changed(before, &after).await {
+            Ok(diff) => {
+                println!(
+                    "[STEP 3] Screen change: {:.1}% (hash distance {}), regions: {:?}",
+                    diff.score * 100.0,
+                    diff.hash_distance,
+                    diff.regions.iter().map(|r| (r.x1, r.y1, r.x2, r.y2)).collect::<Vec<_>>()
+                );
+                Some((after, diff))
+            }
+            Err(e) => {
+                println!("[STEP 3] Screen diff failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Ask the vision model whether the goal is achieved on the screen after
+    /// the action. When it isn't, the thinker hears what the screen shows.
+    async fn verify_goal(&mut self, goal: &Goal, action: &AtomicAction, after: &ScreenState, diff: &ScreenDiff) -> bool {
+        match self.verifier.verify(&self.app_handle, goal, action, after, diff).await {
+            Ok(verification) => {
+                println!(
+                    "[STEP 4] {} ({}): {}",
+                    if verification.goal_achieved { "ACHIEVED" } else { "NOT_ACHIEVED" },
+                    if verification.progress_made { "progress" } else { "no progress" },
+                    verification.observation
+                );
+                self.emit_verification(&verification);
+                if !verification.goal_achieved {
+                    self.thinker.record_verification(&verification);
+                }
+                verification.goal_achieved
+            }
+            Err(e) => {
+                // Without an answer, go by whether the action did anything
+                println!("[STEP 4] Verification failed, going by the screen change: {}", e);
+                if !diff.changed() {
+                    self.thinker.record_error("nothing changed on screen");
+                }
+                diff.changed()
+            }
+        }
+    }
+
     // === State Management ===
 
     fn update_state(&mut self, state: AgentState) {
@@ -295,6 +369,14 @@ impl AgentOrchestrator {
         self.emit_session_update();
     }
 
+    /// Stop after a cancel. Whatever the cancel interrupted is not an error.
+    fn end_cancelled(&mut self) -> Result<(), String> {
+        println!("[AGENT] Cancelled by user");
+        usage::log_summary();
+        self.update_state(AgentState::Cancelled);
+        Err(cancel::CANCELLED_MESSAGE.to_string())
+    }
+
     fn set_error(&mut self, message: &str) {
         self.session.state = AgentState::Error;
         self.session.error = Some(message.to_string());
@@ -303,7 +385,8 @@ impl AgentOrchestrator {
 
     // === Event Emission ===
 
-    fn emit_session_update(&self) {
+    fn emit_session_update(&mut self) {
+        self.session.usage = usage::snapshot();
         let _ = self.app_handle.emit("agent_session", &self.session);
     }
 
@@ -357,9 +440,13 @@ impl AgentOrchestrator {
     }
 }
 
+/// Time the screen gets to react before it's compared with the capture
+/// taken before the action
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
 /// Execute an atomic action
-async fn execute_atomic(action: &AtomicAction) -> ActionResult {
-    let success = match &action.params {
+async fn execute_atomic(action: &AtomicAction) -> Result<(), String> {
+    match &action.params {
         ActionParams::OpenApp { app_name } => computer::open_app(app_name),
         ActionParams::TypeText { text } => computer::type_text(text),
         ActionParams::PressKey { key, modifiers } => {
@@ -374,22 +461,15 @@ async fn execute_atomic(action: &AtomicAction) -> ActionResult {
             computer::mouse_click(*x, *y, btn)
         }
         ActionParams::MouseMove { x, y } => computer::mouse_move(*x, *y),
-        ActionParams::Wait { ms } => {
-            sleep(Duration::from_millis(*ms)).await;
-            Ok(())
-        }
+        ActionParams::Wait { ms } => cancel::sleep(Duration::from_millis(*ms)).await.map_err(String::from),
         ActionParams::FindAndClick { element: _ } => {
             // This shouldn't happen in the new architecture
             // The thinker should output click X Y instead
             Err("FindAndClick is not supported in agent mode".to_string())
         }
-    };
-
-    ActionResult {
-        action_id: action.id.clone(),
-        success: success.is_ok(),
-        error_message: success.err(),
-        screen_changed: true, // We assume screen changed; will verify later
+        ActionParams::FindImageAndClick { image_path } => vision::find_image(image_path)
+            .await
+            .and_then(|element| computer::mouse_click(element.x, element.y, MouseButton::Left)),
     }
 }
 
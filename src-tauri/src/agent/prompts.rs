@@ -4,34 +4,20 @@
 /// Prompt for decomposing a user command into goals
 pub fn decomposition_prompt(command: &str) -> String {
     format!(
-        r#"Break command into goals. FORMAT: "N. what to do | how to know it worked"
-
-IMPORTANT: Always use | as separator, NOT - or :
+        r#"Break command into goals. For each goal give what to do and how to know it worked.
+Output JSON: {{"goals": [{{"description": "...", "success_criteria": "..."}}]}}
 
 Command: open safari
-Goals:
-1. Open Safari browser | Safari window is visible
+Goals: {{"goals": [{{"description": "Open Safari browser", "success_criteria": "Safari window is visible"}}]}}
 
 Command: open chrome and search for rust
-Goals:
-1. Open Chrome browser | Chrome window is visible
-2. Focus URL bar | Cursor is in URL bar
-3. Type "rust" | Text appears in URL bar
-4. Press Enter | Search results page loads
+Goals: {{"goals": [{{"description": "Open Chrome browser", "success_criteria": "Chrome window is visible"}}, {{"description": "Focus URL bar", "success_criteria": "Cursor is in URL bar"}}, {{"description": "Type \"rust\"", "success_criteria": "Text appears in URL bar"}}, {{"description": "Press Enter", "success_criteria": "Search results page loads"}}]}}
 
 Command: check slack and read messages from John
-Goals:
-1. Open Slack | Slack window is visible
-2. Find John's messages | John's message is visible on screen
-
-Command: open messages and see what mom said
-Goals:
-1. Open Messages | Messages app is visible
-2. Find conversation with mom | Mom's conversation is shown
+Goals: {{"goals": [{{"description": "Open Slack", "success_criteria": "Slack window is visible"}}, {{"description": "Find John's messages", "success_criteria": "John's message is visible on screen"}}]}}
 
 Command: {}
-Goals:
-"#,
+Goals: "#,
         command
     )
 }
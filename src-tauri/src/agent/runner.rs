@@ -1,58 +1,69 @@
 use crate::agent::tools::{
-    AgentSession, AgentState, Plan, ScrollDirection, Tool, ToolOutput, ToolResult,
-    UIElement,
+    AgentSession, AgentState, Plan, ScrollDirection, Tool, ToolList, ToolOutput, ToolResult, UIElement,
 };
+use crate::cancel;
 use crate::computer;
-use crate::llm::call_ollama_with_debug;
-use crate::screenshot;
+use crate::llm::{
+    call_chat_with_schema, call_ollama_with_debug, parse_structured, repair, schema_for, usage, ChatMessage, LlmError,
+};
+use crate::settings;
 use crate::types::{LlmCallType, MouseButton};
 use crate::vision;
 use serde::Serialize;
 use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{AppHandle, Emitter};
 use tokio::time::{sleep, Duration};
 
-static CANCELLED: AtomicBool = AtomicBool::new(false);
+/// System prompt for the tool-calling conversation
+const TOOL_SYSTEM_PROMPT: &str = r#"You are a macOS automation agent. Execute actions step by step.
 
-const MAX_STEPS: usize = 50;
+TOOLS:
+- open_app {"name": "Safari"}: Open an application
+- key {"key": "l", "modifiers": ["cmd"]}: Press key combo (for URL bar: cmd+l)
+- type {"text": "hello"}: Type text
+- key {"key": "return"}: Press enter
+- click_image {"path": "/Users/me/icon.png"}: Click where the image in this PNG file appears on screen
+- wait {"ms": 500}: Wait
+- step_done: Mark current step DONE and move to next
 
-pub fn cancel() {
-    CANCELLED.store(true, Ordering::SeqCst);
-}
+Each tool call is written as {"tool": "...", "params": {...}}.
 
-fn is_cancelled() -> bool {
-    CANCELLED.load(Ordering::SeqCst)
-}
+Examples:
+Step: "Open Safari" -> {"tools": [{"tool": "open_app", "params": {"name": "Safari"}}, {"tool": "wait", "params": {"ms": 500}}]}
+Step: "Focus URL bar" -> {"tools": [{"tool": "key", "params": {"key": "l", "modifiers": ["cmd"]}}]}
+Step: "Type hello and search" -> {"tools": [{"tool": "type", "params": {"text": "hello"}}, {"tool": "key", "params": {"key": "return"}}]}
 
-/// A single step in execution history
-#[derive(Debug, Clone, Serialize)]
-struct Step {
-    tool: String,
-    params: Option<serde_json::Value>,
-    success: bool,
-    output: Option<String>,
-    error: Option<String>,
-}
+CRITICAL RULES:
+1. If a tool result is OK, it WORKED. Move to NEXT action, never repeat!
+2. After completing all actions for current step, use step_done
+3. Browser search flow: open_app -> wait -> key cmd+l -> type -> key return -> step_done
+4. Output ONLY JSON, no explanations"#;
 
 pub struct Agent {
     app_handle: AppHandle,
     session: AgentSession,
-    history: Vec<Step>,
+    /// Conversation so far: system prompt, task, tool calls and their results
+    messages: Vec<ChatMessage>,
 }
 
 impl Agent {
     pub fn new(app_handle: AppHandle, task: String) -> Self {
+        let messages = vec![
+            ChatMessage::system(TOOL_SYSTEM_PROMPT),
+            ChatMessage::user(format!("TASK: {}", task)),
+        ];
         Self {
             app_handle,
             session: AgentSession::new(task),
-            history: vec![],
+            messages,
         }
     }
 
     /// Main agent loop: Plan then Execute
     pub async fn run(&mut self) -> Result<String, String> {
-        CANCELLED.store(false, Ordering::SeqCst);
+        cancel::reset();
+        usage::reset();
+        let max_steps = settings::current().limits.max_steps;
 
         println!("\n========================================");
         println!("[AGENT] Starting task: \"{}\"", self.session.task);
@@ -72,6 +83,7 @@ impl Agent {
                 }
                 p
             }
+            Err(_) if cancel::is_cancelled() => return self.end_cancelled(),
             Err(e) => {
                 println!("[PHASE 1] Planning failed: {}", e);
                 self.session.state = AgentState::Failed;
@@ -81,6 +93,15 @@ impl Agent {
             }
         };
 
+        // The plan becomes the assistant's first turn
+        let plan_text = plan
+            .steps
+            .iter()
+            .map(|s| format!("{}. {}", s.id + 1, s.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.messages.push(ChatMessage::assistant(format!("PLAN:\n{}", plan_text)));
+
         self.session.plan = Some(plan);
         self.session.state = AgentState::Executing;
         self.emit_session();
@@ -93,31 +114,49 @@ impl Agent {
             .unwrap_or_default();
 
         for (step_idx, step_desc) in plan_steps.iter().enumerate() {
-            if is_cancelled() {
-                println!("[AGENT] Cancelled by user");
-                self.session.state = AgentState::Failed;
-                self.session.error = Some("Cancelled by user".into());
-                self.emit_session();
-                return Err("Cancelled".into());
+            if cancel::is_cancelled() {
+                return self.end_cancelled();
             }
 
             println!("\n[STEP {}/{}] {}", step_idx + 1, plan_steps.len(), step_desc);
 
+            self.messages.push(ChatMessage::user(format!(
+                "STEP {}/{}: {}\nWhat tools are needed for this step? Output ONLY JSON: {{\"tools\": [...]}}",
+                step_idx + 1,
+                plan_steps.len(),
+                step_desc
+            )));
+
             // Get tools for this step
             let tools = match self.plan_step_tools(step_desc).await {
                 Ok(t) => t,
                 Err(e) => {
                     println!("  Failed to plan tools: {}", e);
+                    // Don't leave the step prompt unanswered in the history
+                    self.messages.pop();
                     continue;
                 }
             };
 
             println!("  Tools: {:?}", tools.iter().map(tool_name).collect::<Vec<_>>());
+            let tool_list = ToolList { tools };
+            self.messages.push(ChatMessage::assistant(
+                serde_json::to_string(&tool_list).unwrap_or_default(),
+            ));
+            let tools = tool_list.tools;
 
             // Execute each tool in sequence
             for tool in tools {
-                if is_cancelled() {
-                    break;
+                if cancel::is_cancelled() {
+                    return self.end_cancelled();
+                }
+
+                if self.session.step_count >= max_steps {
+                    println!("[AGENT] Maximum steps ({}) exceeded", max_steps);
+                    self.session.state = AgentState::Failed;
+                    self.session.error = Some("Maximum steps exceeded".into());
+                    self.emit_session();
+                    return Err("Maximum steps exceeded".into());
                 }
 
                 println!("  [EXEC] {}", tool_name(&tool));
@@ -126,6 +165,7 @@ impl Agent {
                 if !result.success {
                     println!("    Failed: {:?}", result.error);
                 }
+                self.messages.push(ChatMessage::tool(format_result(&result)));
 
                 self.session.step_count += 1;
                 self.emit_session();
@@ -139,9 +179,16 @@ impl Agent {
             }
         }
 
+        // Cancelled while planning the last step's tools
+        if cancel::is_cancelled() {
+            return self.end_cancelled();
+        }
+
         println!("\n========================================");
         println!("[AGENT] All plan steps completed!");
         println!("========================================\n");
+        usage::log_summary();
+        repair::log_summary();
 
         self.session.state = AgentState::Done;
         self.emit_session();
@@ -150,6 +197,15 @@ impl Agent {
         Ok(summary)
     }
 
+    /// Stop after a cancel. Whatever the cancel interrupted is not an error.
+    fn end_cancelled(&mut self) -> Result<String, String> {
+        println!("[AGENT] Cancelled by user");
+        usage::log_summary();
+        self.session.state = AgentState::Cancelled;
+        self.emit_session();
+        Err(cancel::CANCELLED_MESSAGE.into())
+    }
+
     /// Plan tools needed for a single step
     async fn plan_step_tools(&self, step_desc: &str) -> Result<Vec<Tool>, String> {
         // First, try pattern matching for common steps
@@ -157,39 +213,20 @@ impl Agent {
             return Ok(tools);
         }
 
-        // Fall back to LLM
-        let prompt = format!(
-            r#"What tools are needed for this step? Output a JSON array.
-
-TASK: {}
-STEP: {}
-
-Available tools:
-- open_app: {{"name": "AppName"}}
-- key: {{"key": "l", "modifiers": ["cmd"]}} or {{"key": "return"}}
-- type: {{"text": "search query"}}
-- wait: {{"ms": 500}}
-
-Examples:
-Step: "Open Safari" -> [{{"tool": "open_app", "params": {{"name": "Safari"}}}}, {{"tool": "wait", "params": {{"ms": 500}}}}]
-Step: "Focus URL bar" -> [{{"tool": "key", "params": {{"key": "l", "modifiers": ["cmd"]}}}}]
-Step: "Type hello and search" -> [{{"tool": "type", "params": {{"text": "hello"}}}}, {{"tool": "key", "params": {{"key": "return"}}}}]
-
-Output ONLY the JSON array:
-"#,
-            self.session.task,
-            step_desc
-        );
-
-        let response = call_ollama_with_debug(
-            &self.app_handle,
-            &prompt,
-            LlmCallType::ActionDecision,
+        // Fall back to LLM, which sees the whole conversation so far
+        let app_handle = &self.app_handle;
+        let schema = schema_for::<ToolList>();
+        let (mut tools, _) = repair::call_with_repair(
+            "step_tools",
+            &self.messages,
+            |messages| {
+                let schema = schema.clone();
+                async move { call_chat_with_schema(app_handle, &messages, LlmCallType::ActionDecision, schema).await }
+            },
+            |response| parse_tools_array(response).map_err(String::from),
         )
         .await?;
 
-        let mut tools = parse_tools_array(&response)?;
-
         // Post-process: ensure search steps end with Enter
         let step_lower = step_desc.to_lowercase();
         if step_lower.contains("search") || step_lower.contains("submit") || step_lower.contains("enter") {
@@ -276,80 +313,28 @@ Output ONLY numbered steps:"#,
         parse_plan(&self.session.task, &response)
     }
 
-    /// Ask LLM to pick next tool based on current state
+    /// Ask LLM to pick next tool based on the conversation so far
     async fn decide_next_tool(&self) -> Result<Tool, String> {
-        let prompt = self.build_tool_prompt();
-
-        let response = call_ollama_with_debug(
-            &self.app_handle,
-            &prompt,
-            LlmCallType::ActionDecision,
-        )
-        .await?;
-
-        parse_tool_response(&response)
-    }
-
-    /// Build prompt for tool selection
-    fn build_tool_prompt(&self) -> String {
         let plan = self.session.plan.as_ref();
         let current_step = plan.and_then(|p| p.current_step_desc()).unwrap_or("Complete the task");
         let step_num = plan.map(|p| p.current_step + 1).unwrap_or(1);
         let total_steps = plan.map(|p| p.steps.len()).unwrap_or(1);
 
-        let mut prompt = format!(
-            r#"You are a macOS automation agent. Execute actions step by step.
-
-TASK: {}
-CURRENT STEP ({}/{}): {}
-
-"#,
-            self.session.task,
-            step_num,
-            total_steps,
-            current_step,
-        );
-
-        // Add recent history (last 5 steps)
-        if !self.history.is_empty() {
-            prompt.push_str("DONE ACTIONS:\n");
-            let start = self.history.len().saturating_sub(5);
-            for (i, step) in self.history[start..].iter().enumerate() {
-                let idx = start + i + 1;
-                let status = if step.success { "OK" } else { "FAILED" };
-                if let Some(params) = &step.params {
-                    prompt.push_str(&format!("{}. {} {} -> {}\n", idx, step.tool, params, status));
-                } else {
-                    prompt.push_str(&format!("{}. {} -> {}\n", idx, step.tool, status));
-                }
-                if let Some(err) = &step.error {
-                    prompt.push_str(&format!("   Error: {}\n", err));
-                }
-            }
-            prompt.push('\n');
-        }
+        let mut messages = self.messages.clone();
+        messages.push(ChatMessage::user(format!(
+            "CURRENT STEP ({}/{}): {}\nWhat is the NEXT action? Output JSON:\n{{\"tool\": \"...\", \"params\": {{...}}}}",
+            step_num, total_steps, current_step
+        )));
 
-        prompt.push_str(
-            r#"TOOLS:
-- open_app {"name": "Safari"}: Open an application
-- key {"key": "l", "modifiers": ["cmd"]}: Press key combo (for URL bar: cmd+l)
-- type {"text": "hello"}: Type text
-- key {"key": "return"}: Press enter
-- wait {"ms": 500}: Wait
-- step_done: Mark current step DONE and move to next
-
-CRITICAL RULES:
-1. If action shows "-> OK", it WORKED. Move to NEXT action, never repeat!
-2. After completing all actions for current step, use step_done
-3. Browser search flow: open_app -> wait -> key cmd+l -> type -> key return -> step_done
-
-What is the NEXT action? Output JSON:
-{"tool": "...", "params": {...}}
-
-JSON:"#,
-        );
+        let response = call_chat_with_schema(
+            &self.app_handle,
+            &messages,
+            LlmCallType::ActionDecision,
+            schema_for::<Tool>(),
+        )
+        .await?;
 
-        prompt
+        Ok(parse_tool_response(&response)?)
     }
 
     /// Execute a tool and return result
@@ -408,6 +393,24 @@ JSON:"#,
                 }
             }
 
+            Tool::ClickImage { path } => {
+                println!("  [EXEC] Click image: {}", path);
+                let result = vision::find_image(path).await.and_then(|element| {
+                    computer::mouse_click(element.x, element.y, MouseButton::Left)?;
+                    Ok(ToolOutput::ImageMatch {
+                        x: element.x,
+                        y: element.y,
+                        confidence: element.confidence,
+                    })
+                });
+                ToolResult {
+                    tool: "click_image".into(),
+                    success: result.is_ok(),
+                    output: result.as_ref().ok().cloned(),
+                    error: result.err(),
+                }
+            }
+
             Tool::Type { text } => {
                 println!("  [EXEC] Type: \"{}\"", text);
                 let result = computer::type_text(text);
@@ -436,12 +439,12 @@ JSON:"#,
 
             Tool::Wait { ms } => {
                 println!("  [EXEC] Wait {}ms", ms);
-                sleep(Duration::from_millis(*ms)).await;
+                let result = cancel::sleep(Duration::from_millis(*ms)).await.map_err(String::from);
                 ToolResult {
                     tool: "wait".into(),
-                    success: true,
-                    output: Some(ToolOutput::Ack),
-                    error: None,
+                    success: result.is_ok(),
+                    output: result.is_ok().then_some(ToolOutput::Ack),
+                    error: result.err(),
                 }
             }
 
@@ -476,21 +479,20 @@ JSON:"#,
 
     /// Capture screen and detect UI elements
     async fn capture_screen(&self) -> Result<(Vec<UIElement>, Option<String>), String> {
-        let (bytes, scale_x, scale_y) =
-            screenshot::capture_and_resize().map_err(|e| e.to_string())?;
+        let vision = vision::backend();
+        let (bytes, scale_x, scale_y) = vision.capture()?;
 
-        let detected = vision::detect_ui_elements(&self.app_handle, &bytes, "").await?;
+        let detected = vision::detect_screen_elements(&self.app_handle, &vision, &bytes, (scale_x, scale_y), "").await?;
 
         let elements: Vec<UIElement> = detected
             .into_iter()
             .map(|e| {
-                let cx = ((e.x1 + e.x2) / 2) as f64 * scale_x;
-                let cy = ((e.y1 + e.y2) / 2) as f64 * scale_y;
+                let (x, y) = e.center();
                 UIElement {
                     label: e.label,
                     element_type: e.element_type,
-                    x: cx as i32,
-                    y: cy as i32,
+                    x,
+                    y,
                 }
             })
             .collect();
@@ -500,7 +502,8 @@ JSON:"#,
         Ok((elements, active_app))
     }
 
-    fn emit_session(&self) {
+    fn emit_session(&mut self) {
+        self.session.usage = usage::snapshot();
         let _ = self.app_handle.emit("agent_session", &self.session);
     }
 
@@ -534,6 +537,7 @@ fn tool_name(tool: &Tool) -> String {
         Tool::Screenshot => "screenshot".into(),
         Tool::Click { .. } => "click".into(),
         Tool::DoubleClick { .. } => "double_click".into(),
+        Tool::ClickImage { .. } => "click_image".into(),
         Tool::Type { .. } => "type".into(),
         Tool::Key { .. } => "key".into(),
         Tool::Wait { .. } => "wait".into(),
@@ -550,6 +554,7 @@ fn tool_params(tool: &Tool) -> Option<serde_json::Value> {
         Tool::Screenshot => None,
         Tool::Click { x, y } => Some(serde_json::json!({"x": x, "y": y})),
         Tool::DoubleClick { x, y } => Some(serde_json::json!({"x": x, "y": y})),
+        Tool::ClickImage { path } => Some(serde_json::json!({"path": path})),
         Tool::Type { text } => Some(serde_json::json!({"text": text})),
         Tool::Key { key, modifiers } => Some(serde_json::json!({"key": key, "modifiers": modifiers})),
         Tool::Wait { ms } => Some(serde_json::json!({"ms": ms})),
@@ -586,10 +591,23 @@ fn format_output(output: &ToolOutput) -> String {
             }
             s
         }
+        ToolOutput::ImageMatch { x, y, confidence } => {
+            format!("clicked at ({}, {}), confidence {:.2}", x, y, confidence)
+        }
         ToolOutput::Ack => "OK".into(),
     }
 }
 
+/// Tool result as reported back to the model
+fn format_result(result: &ToolResult) -> String {
+    match (&result.output, &result.error) {
+        (_, Some(error)) => format!("{} FAILED: {}", result.tool, error),
+        (Some(output), None) if result.success => format!("{} {}", result.tool, format_output(output)),
+        _ if result.success => format!("{} OK", result.tool),
+        _ => format!("{} FAILED", result.tool),
+    }
+}
+
 /// Extract search query from task string
 fn extract_search_query(task: &str) -> Option<String> {
     // Pattern: "search X", "search for X", "search X in Y"
@@ -615,19 +633,35 @@ fn extract_search_query(task: &str) -> Option<String> {
     None
 }
 
-/// Parse array of tools from LLM response
-fn parse_tools_array(response: &str) -> Result<Vec<Tool>, String> {
+/// Parse the tools for a step from a schema-constrained LLM response.
+/// Falls back to the lenient parser when the output misses the schema.
+fn parse_tools_array(response: &str) -> Result<Vec<Tool>, LlmError> {
+    let schema_err = match parse_structured::<ToolList>(response) {
+        Ok(list) if !list.tools.is_empty() => return Ok(list.tools),
+        Ok(_) => LlmError::SchemaViolation {
+            target: "ToolList",
+            message: "no tools in response".into(),
+        },
+        Err(e) => e,
+    };
+    match parse_tools_lenient(response) {
+        Ok(tools) => {
+            println!("[AGENT] Tool list missed the schema ({}), parsed it leniently", schema_err);
+            Ok(tools)
+        }
+        Err(_) => Err(schema_err),
+    }
+}
+
+/// Parse a loose JSON array of tools, filling in defaults for missing
+/// params and skipping tools we don't know
+fn parse_tools_lenient(response: &str) -> Result<Vec<Tool>, String> {
     let response = response.trim();
 
     // Find JSON array in response
-    let json_str = if let Some(start) = response.find('[') {
-        if let Some(end) = response.rfind(']') {
-            &response[start..=end]
-        } else {
-            return Err("No closing bracket found".into());
-        }
-    } else {
-        return Err("No JSON array found".into());
+    let json_str = match (response.find('['), response.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => return Err("No JSON array found".into()),
     };
 
     // Parse as array of generic objects
@@ -636,60 +670,51 @@ fn parse_tools_array(response: &str) -> Result<Vec<Tool>, String> {
 
     let mut tools = Vec::new();
     for obj in arr {
-        if let Some(tool_name) = obj.get("tool").and_then(|v| v.as_str()) {
-            let params = obj.get("params").cloned();
-
-            let tool = match tool_name {
-                "open_app" => {
-                    let name = params.as_ref()
-                        .and_then(|p| p.get("name"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("Safari")
-                        .to_string();
-                    Tool::OpenApp { name }
-                }
-                "key" => {
-                    let key = params.as_ref()
-                        .and_then(|p| p.get("key"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("return")
-                        .to_string();
-                    let modifiers = params.as_ref()
-                        .and_then(|p| p.get("modifiers"))
-                        .and_then(|v| v.as_array())
-                        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect());
-                    Tool::Key { key, modifiers }
-                }
-                "type" => {
-                    let text = params.as_ref()
-                        .and_then(|p| p.get("text"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    Tool::Type { text }
-                }
-                "wait" => {
-                    let ms = params.as_ref()
-                        .and_then(|p| p.get("ms"))
-                        .and_then(|v| v.as_u64())
-                        .unwrap_or(500);
-                    Tool::Wait { ms }
-                }
-                "click" => {
-                    let x = params.as_ref()
-                        .and_then(|p| p.get("x"))
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(0) as i32;
-                    let y = params.as_ref()
-                        .and_then(|p| p.get("y"))
-                        .and_then(|v| v.as_i64())
-                        .unwrap_or(0) as i32;
-                    Tool::Click { x, y }
-                }
-                _ => continue,
-            };
+        if let Ok(tool) = serde_json::from_value::<Tool>(obj.clone()) {
             tools.push(tool);
+            continue;
         }
+        let Some(tool_name) = obj.get("tool").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let params = obj.get("params").cloned();
+        let str_param = |name: &str, default: &str| {
+            params.as_ref()
+                .and_then(|p| p.get(name))
+                .and_then(|v| v.as_str())
+                .unwrap_or(default)
+                .to_string()
+        };
+        let int_param = |name: &str| {
+            params.as_ref()
+                .and_then(|p| p.get(name))
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0) as i32
+        };
+
+        let tool = match tool_name {
+            "screenshot" => Tool::Screenshot,
+            "open_app" => Tool::OpenApp { name: str_param("name", "Safari") },
+            "key" => {
+                let modifiers = params.as_ref()
+                    .and_then(|p| p.get("modifiers"))
+                    .and_then(|v| v.as_array())
+                    .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect());
+                Tool::Key { key: str_param("key", "return"), modifiers }
+            }
+            "type" => Tool::Type { text: str_param("text", "") },
+            "wait" => {
+                let ms = params.as_ref()
+                    .and_then(|p| p.get("ms"))
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(500);
+                Tool::Wait { ms }
+            }
+            "click" => Tool::Click { x: int_param("x"), y: int_param("y") },
+            "step_done" => Tool::StepDone,
+            _ => continue,
+        };
+        tools.push(tool);
     }
 
     if tools.is_empty() {
@@ -734,8 +759,23 @@ fn parse_plan(task: &str, response: &str) -> Result<Plan, String> {
     Ok(Plan::new(task.to_string(), steps))
 }
 
-/// Parse tool from LLM response
-fn parse_tool_response(response: &str) -> Result<Tool, String> {
+/// Parse a single tool from a schema-constrained LLM response.
+/// Falls back to the lenient parser when the output misses the schema.
+fn parse_tool_response(response: &str) -> Result<Tool, LlmError> {
+    match parse_structured(response) {
+        Ok(tool) => Ok(tool),
+        Err(schema_err) => match parse_tool_lenient(response) {
+            Ok(tool) => {
+                println!("[AGENT] Tool response missed the schema ({}), parsed it leniently", schema_err);
+                Ok(tool)
+            }
+            Err(_) => Err(schema_err),
+        },
+    }
+}
+
+/// Parse a tool from loose JSON, filling in defaults for missing params
+fn parse_tool_lenient(response: &str) -> Result<Tool, String> {
     let response = response.trim();
 
     // Try to find JSON in response
@@ -787,6 +827,15 @@ fn parse_tool_response(response: &str) -> Result<Tool, String> {
                         .ok_or("double_click requires y coordinate")? as i32;
                     Ok(Tool::DoubleClick { x, y })
                 }
+                "click_image" => {
+                    let path = params
+                        .as_ref()
+                        .and_then(|p| p.get("path"))
+                        .and_then(|v| v.as_str())
+                        .ok_or("click_image requires path")?
+                        .to_string();
+                    Ok(Tool::ClickImage { path })
+                }
                 "type" => {
                     let text = params
                         .as_ref()
@@ -880,3 +929,44 @@ fn parse_tool_response(response: &str) -> Result<Tool, String> {
         response
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_response_falls_back_to_lenient_defaults() {
+        assert!(matches!(
+            parse_tool_response(r#"{"tool": "wait"}"#),
+            Ok(Tool::Wait { ms: 500 })
+        ));
+        assert!(matches!(
+            parse_tool_response(r#"Next: {"tool": "done", "params": {}}"#),
+            Ok(Tool::Done { ref summary }) if summary == "Task completed"
+        ));
+        assert!(matches!(
+            parse_tool_response(r#"{"tool": "click", "params": {"x": 10, "y": 20}}"#),
+            Ok(Tool::Click { x: 10, y: 20 })
+        ));
+        assert!(parse_tool_response(r#"{"tool": "fly"}"#).is_err());
+    }
+
+    #[test]
+    fn test_tools_array_falls_back_to_lenient_defaults() {
+        let tools = parse_tools_array(
+            r#"[{"tool": "open_app"}, {"tool": "fly"}, {"tool": "key", "params": {"key": "tab"}}, {"tool": "wait"}]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            tools.as_slice(),
+            [Tool::OpenApp { name }, Tool::Key { key, modifiers: None }, Tool::Wait { ms: 500 }]
+                if name == "Safari" && key == "tab"
+        ));
+        assert!(matches!(
+            parse_tools_array(r#"{"tools": [{"tool": "type"}]}"#).as_deref(),
+            Ok([Tool::Type { text }]) if text.is_empty()
+        ));
+        assert!(parse_tools_array(r#"[{"tool": "fly"}]"#).is_err());
+        assert!(parse_tools_array("no tools here").is_err());
+    }
+}
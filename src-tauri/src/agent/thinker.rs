@@ -1,29 +1,17 @@
 use crate::computer;
-use crate::llm;
-use crate::types::{ActionParams, ActionResult, ActionType, AtomicAction, Goal, LlmCallType, ScreenState};
+use crate::llm::{self, repair, ChatMessage};
+use crate::settings;
+use crate::types::{
+    self, ActionParams, ActionResult, ActionType, AtomicAction, ElementKind, Goal, LlmCallType, ScreenState, Screenshot,
+    UIElement, VerificationResult,
+};
+use crate::vision::{self, VisionBackend};
 use regex::Regex;
+use std::sync::Arc;
 use tauri::AppHandle;
 
-pub struct Thinker;
-
-impl Thinker {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// Decide action WITHOUT seeing the screen (first pass)
-    /// Returns an action that may or may not need coordinates
-    pub async fn decide_action_blind(
-        &self,
-        app_handle: &AppHandle,
-        goal: &Goal,
-    ) -> Result<AtomicAction, String> {
-        println!("[THINKER] Deciding action BLIND for goal: \"{}\"", goal.description);
-
-        let prompt = format!(
-            r#"Goal: {}
-
-Pick ONE action to achieve this goal.
+/// System prompt for blind action decisions
+const BLIND_SYSTEM_PROMPT: &str = r#"You control a Mac. For each goal, pick ONE action at a time.
 Output ONLY the action, nothing else.
 
 Actions:
@@ -40,20 +28,111 @@ Examples:
 - Press Cmd+L: key CMD+L
 - Check messages: open Messages
 
-Action:"#,
-            goal.description
-        );
+After each action you get its result. If it FAILED, try something different."#;
+
+/// GBNF grammar for the blind action DSL above. Backends that support
+/// grammars (llama.cpp-server, embedded) can only produce lines
+/// `parse_action_blind` accepts; Ollama has no GBNF support and answers freely.
+const ACTION_GRAMMAR: &str = r#"root    ::= action "\n"?
+action  ::= open | click | type | key | wait
+open    ::= "open " text
+click   ::= "click " text
+type    ::= "type \"" [^"\n]+ "\""
+key     ::= "key " keyname (" "? "+" " "? keyname)*
+keyname ::= [^+ \n]+ (" " [^+ \n]+)*
+wait    ::= "wait " [0-9] [0-9]? [0-9]? [0-9]? [0-9]?
+text    ::= [^\n]+
+"#;
+
+pub struct Thinker {
+    /// Conversation for the current goal: system prompt, goal, actions and results
+    messages: Vec<ChatMessage>,
+    goal_id: Option<String>,
+    /// Picks among candidate elements by Set-of-Mark
+    vision: Arc<dyn VisionBackend>,
+}
 
-        println!("[THINKER] Calling LLM (blind)...");
-        let response = llm::call_ollama_with_debug(app_handle, &prompt, LlmCallType::ActionDecision).await?;
-        println!("[THINKER] LLM raw response: \"{}\"", response.lines().next().unwrap_or(""));
+impl Thinker {
+    pub fn new() -> Self {
+        Self::with_vision(vision::backend())
+    }
+
+    /// A thinker that resolves ambiguous clicks through `vision`
+    pub fn with_vision(vision: Arc<dyn VisionBackend>) -> Self {
+        Self {
+            messages: vec![],
+            goal_id: None,
+            vision,
+        }
+    }
 
-        let action = parse_action_blind(&response, goal)?;
+    /// Start a fresh conversation when the goal changes
+    fn start_goal(&mut self, goal: &Goal) {
+        if self.goal_id.as_deref() == Some(goal.id.as_str()) {
+            return;
+        }
+        self.goal_id = Some(goal.id.clone());
+        self.messages = vec![
+            ChatMessage::system(BLIND_SYSTEM_PROMPT),
+            ChatMessage::user(format!("Goal: {}\n\nAction:", goal.description)),
+        ];
+    }
+
+    /// Decide action WITHOUT seeing the screen (first pass)
+    /// Returns an action that may or may not need coordinates
+    pub async fn decide_action_blind(
+        &mut self,
+        app_handle: &AppHandle,
+        goal: &Goal,
+    ) -> Result<AtomicAction, String> {
+        println!("[THINKER] Deciding action BLIND for goal: \"{}\"", goal.description);
+        self.start_goal(goal);
+
+        println!("[THINKER] Calling LLM (blind, {} messages)...", self.messages.len());
+        // Still parsed by hand: backends without grammar support answer freely
+        let (action, response) = repair::call_with_repair(
+            "action_blind",
+            &self.messages,
+            |messages| async move {
+                llm::call_chat_with_grammar(app_handle, &messages, LlmCallType::ActionDecision, ACTION_GRAMMAR).await
+            },
+            |response| parse_action_blind(response, goal),
+        )
+        .await?;
+        println!("[THINKER] LLM raw response: \"{}\"", response.lines().next().unwrap_or(""));
         println!("[THINKER] Parsed action: {:?} -> {:?}", action.action_type, action.params);
 
+        // Only well-formed actions become part of the conversation
+        self.messages.push(ChatMessage::assistant(response.lines().next().unwrap_or("").trim()));
+
         Ok(action)
     }
 
+    /// Report the outcome of the last action back to the conversation
+    pub fn record_result(&mut self, result: &ActionResult) {
+        let outcome = if result.success {
+            "OK".to_string()
+        } else {
+            format!("FAILED: {}", result.error_message.as_deref().unwrap_or("unknown error"))
+        };
+        self.messages.push(ChatMessage::tool(outcome));
+    }
+
+    /// Report that the last action ran but the goal isn't achieved, with
+    /// what the screen shows now
+    pub fn record_verification(&mut self, verification: &VerificationResult) {
+        let progress = if verification.progress_made { "some progress" } else { "no progress" };
+        self.messages.push(ChatMessage::tool(format!(
+            "NOT_ACHIEVED ({}): {}",
+            progress, verification.observation
+        )));
+    }
+
+    /// Report that the last action could not be carried out
+    pub fn record_error(&mut self, error: &str) {
+        self.messages.push(ChatMessage::tool(format!("FAILED: {}", error)));
+    }
+
     /// Decide action WITH screen info (for actions that need coordinates)
     pub async fn decide_action_with_screen(
         &self,
@@ -65,7 +144,22 @@ Action:"#,
         println!("[THINKER] Finding \"{}\" on screen", element_to_find);
         println!("[THINKER] UI elements detected: {}", screen.ui_elements.len());
 
-        let ui_elements_str = screen.format_ui_elements();
+        let candidates = match wanted_kind(goal, element_to_find) {
+            Some(kind) => {
+                let candidates = screen.elements_of_kind(&kind);
+                println!("[THINKER] Looking for a {}: {} candidates", kind, candidates.len());
+                candidates
+            }
+            None => screen.ui_elements.clone(),
+        };
+
+        if let Some(screenshot) = &screen.screenshot {
+            if settings::current().vision.set_of_mark && !candidates.is_empty() {
+                return self.decide_click_by_mark(app_handle, goal, element_to_find, &candidates, screenshot).await;
+            }
+        }
+
+        let ui_elements_str = types::format_elements(&candidates);
 
         let prompt = format!(
             r#"I need to click: "{}"
@@ -84,15 +178,49 @@ Answer:"#,
         );
 
         println!("[THINKER] Calling LLM (with screen)...");
-        let response = llm::call_ollama_with_debug(app_handle, &prompt, LlmCallType::ActionDecision).await?;
+        let messages = [ChatMessage::user(prompt)];
+        let (action, response) = repair::call_with_repair(
+            "click_action",
+            &messages,
+            |messages| async move {
+                llm::call_chat_streaming(app_handle, &messages, LlmCallType::ActionDecision, |_| false).await
+            },
+            |response| parse_click_action(response, goal, element_to_find),
+        )
+        .await?;
         println!("[THINKER] LLM raw response: \"{}\"", response.lines().next().unwrap_or(""));
 
-        let action = parse_click_action(&response, goal, element_to_find)?;
+        let action = action.ok_or_else(|| format!("Could not find '{}' on screen", element_to_find))?;
         println!("[THINKER] Parsed action: {:?} -> {:?}", action.action_type, action.params);
 
         Ok(action)
     }
 
+    /// Set-of-Mark: the vision model picks the numbered box to click
+    async fn decide_click_by_mark(
+        &self,
+        app_handle: &AppHandle,
+        goal: &Goal,
+        element_to_find: &str,
+        candidates: &[UIElement],
+        screenshot: &Screenshot,
+    ) -> Result<AtomicAction, String> {
+        println!("[THINKER] Asking vision model to pick a mark...");
+        let index = vision::select_mark(app_handle, self.vision.as_ref(), screenshot, candidates, element_to_find)
+            .await?
+            .ok_or_else(|| format!("Could not find '{}' on screen", element_to_find))?;
+
+        let element = &candidates[index];
+        let (x, y) = element.center();
+        println!("[THINKER] Picked mark {}: {} '{}' at ({}, {})", index + 1, element.element_type, element.label, x, y);
+
+        Ok(AtomicAction::new(
+            ActionType::MouseClick,
+            ActionParams::MouseClick { x, y, button: None },
+            format!("Clicking '{}' (mark {}) at ({}, {}) for: {}", element_to_find, index + 1, x, y, goal.description),
+        ))
+    }
+
     /// Legacy: Decide action with full screen state (for complex scenarios)
     pub async fn decide_action(
         &self,
@@ -104,7 +232,7 @@ Answer:"#,
         println!("[THINKER] Deciding action for goal: \"{}\"", goal.description);
         println!("[THINKER] UI elements detected: {}", screen.ui_elements.len());
 
-        let ui_elements_str = screen.format_ui_elements();
+        let ui_elements_str = types::format_elements(&screen.ui_elements);
 
         let prompt = format!(
             r#"Goal: {}
@@ -137,7 +265,8 @@ Action:"#,
         );
 
         println!("[THINKER] Calling LLM...");
-        let response = llm::call_ollama_with_debug(app_handle, &prompt, LlmCallType::ActionDecision).await?;
+        let messages = [ChatMessage::user(prompt)];
+        let response = llm::call_chat_streaming(app_handle, &messages, LlmCallType::ActionDecision, |_| false).await?;
         println!("[THINKER] LLM raw response: \"{}\"", response.lines().next().unwrap_or(""));
 
         let action = parse_action(&response, goal)?;
@@ -147,6 +276,18 @@ Action:"#,
     }
 }
 
+/// Kind of element to click: the one the description names, else a text
+/// field when the goal is to type something
+fn wanted_kind(goal: &Goal, element_to_find: &str) -> Option<ElementKind> {
+    ElementKind::mentioned_in(element_to_find).or_else(|| {
+        let goal = goal.description.to_lowercase();
+        ["type ", "enter ", "fill ", "write "]
+            .iter()
+            .any(|verb| goal.starts_with(verb))
+            .then_some(ElementKind::TextField)
+    })
+}
+
 /// Format recent actions for prompt context
 fn format_recent_actions(history: &[ActionResult]) -> String {
     if history.is_empty() {
@@ -262,24 +403,25 @@ fn parse_action_blind(response: &str, goal: &Goal) -> Result<AtomicAction, Strin
     ))
 }
 
-/// Parse click action when we have screen coordinates
-fn parse_click_action(response: &str, goal: &Goal, element: &str) -> Result<AtomicAction, String> {
+/// Parse click action when we have screen coordinates.
+/// None when the model says the element isn't on screen.
+fn parse_click_action(response: &str, goal: &Goal, element: &str) -> Result<Option<AtomicAction>, String> {
     let line = response.lines().next().unwrap_or("").trim().to_lowercase();
 
     // Parse: click X Y
     if let Some(caps) = Regex::new(r"^click\s+(\d+)\s+(\d+)$").ok().and_then(|re| re.captures(&line)) {
         let x: i32 = caps.get(1).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
         let y: i32 = caps.get(2).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
-        return Ok(AtomicAction::new(
+        return Ok(Some(AtomicAction::new(
             ActionType::MouseClick,
             ActionParams::MouseClick { x, y, button: None },
             format!("Clicking '{}' at ({}, {}) for: {}", element, x, y, goal.description),
-        ));
+        )));
     }
 
     // not_found response
     if line.contains("not_found") || line.contains("no match") {
-        return Err(format!("Could not find '{}' on screen", element));
+        return Ok(None);
     }
 
     Err(format!(
@@ -465,4 +607,62 @@ mod tests {
         assert_eq!(key, "n");
         assert_eq!(mods, Some(vec!["cmd".to_string(), "shift".to_string()]));
     }
+
+    #[test]
+    fn test_action_grammar_rules_are_defined() {
+        // String literals and character classes; what's left are rule names
+        let literals = Regex::new(r#""(\\.|[^"\\])*"|\[[^\]]*\]"#).unwrap();
+        let rule_name = Regex::new(r"[a-z]+").unwrap();
+        let mut defined = vec![];
+        let mut used = vec![];
+        for line in ACTION_GRAMMAR.lines().filter(|l| !l.trim().is_empty()) {
+            let (name, body) = line.split_once("::=").unwrap();
+            defined.push(name.trim().to_string());
+            let body = literals.replace_all(body, " ");
+            used.extend(rule_name.find_iter(&body).map(|m| m.as_str().to_string()));
+        }
+        assert!(defined.contains(&"root".to_string()));
+        for name in used {
+            assert!(defined.contains(&name), "rule {} is not defined", name);
+        }
+    }
+
+    #[test]
+    fn test_grammar_shaped_actions_parse() {
+        let goal = Goal::new("Search for rust".into(), "Results are visible".into());
+        let parse = |response: &str| parse_action_blind(response, &goal).unwrap().action_type;
+        assert!(matches!(parse("click submit button"), ActionType::FindAndClick));
+        assert!(matches!(parse("click 450 320"), ActionType::MouseClick));
+        assert!(matches!(parse("type \"rust\"\n"), ActionType::TypeText));
+        assert!(matches!(parse("key CMD+SHIFT+N"), ActionType::PressKey));
+        assert!(matches!(parse("wait 500"), ActionType::Wait));
+
+        // Spaced combos and multi-word keys, which the grammar allows too
+        let params = |response: &str| parse_action_blind(response, &goal).unwrap().params;
+        assert!(matches!(
+            params("key CMD + L"),
+            ActionParams::PressKey { ref key, modifiers: Some(ref mods) } if key == "l" && mods == &["cmd"]
+        ));
+        assert!(matches!(
+            params("key page down"),
+            ActionParams::PressKey { ref key, modifiers: None } if key == "page down"
+        ));
+    }
+
+    #[test]
+    fn test_wanted_kind() {
+        let search = Goal::new("Search for rust".into(), "Results are visible".into());
+        assert_eq!(wanted_kind(&search, "the search box"), Some(ElementKind::TextField));
+        assert_eq!(wanted_kind(&search, "Submit button"), Some(ElementKind::Button));
+        assert_eq!(wanted_kind(&search, "File menu bar item"), Some(ElementKind::Menu));
+        assert_eq!(wanted_kind(&search, "Rust"), None);
+        assert_eq!(wanted_kind(&search, "the page title"), None);
+        assert_eq!(wanted_kind(&search, "Remember me check box"), Some(ElementKind::Checkbox));
+
+        let typing = Goal::new("Type hello in the chat".into(), "Message is typed".into());
+        assert_eq!(wanted_kind(&typing, "message"), Some(ElementKind::TextField));
+        assert_eq!(wanted_kind(&typing, "Send button"), Some(ElementKind::Button));
+        let typing_text = Goal::new("Type text in the search box".into(), "Text is typed".into());
+        assert_eq!(wanted_kind(&typing_text, "search box"), Some(ElementKind::TextField));
+    }
 }
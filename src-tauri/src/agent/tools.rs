@@ -1,10 +1,13 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::types::{ElementKind, SessionUsage};
+
 // ==========================================
 // Tools - All available tools for the agent
 // ==========================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "tool", content = "params", rename_all = "snake_case")]
 pub enum Tool {
     // === Perception ===
@@ -18,6 +21,9 @@ pub enum Tool {
     /// Double click at screen coordinates
     DoubleClick { x: i32, y: i32 },
 
+    /// Click where a reference image (PNG file path) appears on screen
+    ClickImage { path: String },
+
     /// Type text
     Type { text: String },
 
@@ -48,7 +54,7 @@ pub enum Tool {
     Fail { reason: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ScrollDirection {
     Up,
@@ -57,6 +63,13 @@ pub enum ScrollDirection {
     Right,
 }
 
+/// Tools for one plan step, as the LLM returns them.
+/// Wrapped in an object because schema-constrained output must be one.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ToolList {
+    pub tools: Vec<Tool>,
+}
+
 // ==========================================
 // Tool Results
 // ==========================================
@@ -77,6 +90,8 @@ pub enum ToolOutput {
         elements: Vec<UIElement>,
         active_app: Option<String>,
     },
+    /// Where a reference image was found and clicked
+    ImageMatch { x: i32, y: i32, confidence: f32 },
     /// Simple acknowledgment
     Ack,
 }
@@ -84,7 +99,7 @@ pub enum ToolOutput {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UIElement {
     pub label: String,
-    pub element_type: String,
+    pub element_type: ElementKind,
     pub x: i32, // center x
     pub y: i32, // center y
 }
@@ -193,6 +208,8 @@ pub struct AgentSession {
     pub plan: Option<Plan>,
     pub step_count: usize,
     pub error: Option<String>,
+    /// LLM tokens and time spent so far
+    pub usage: SessionUsage,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -203,6 +220,8 @@ pub enum AgentState {
     Executing,
     Done,
     Failed,
+    /// Stopped by the user
+    Cancelled,
 }
 
 impl AgentSession {
@@ -214,6 +233,7 @@ impl AgentSession {
             plan: None,
             step_count: 0,
             error: None,
+            usage: SessionUsage::default(),
         }
     }
 }
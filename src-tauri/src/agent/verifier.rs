@@ -1,54 +1,93 @@
 use crate::types::{AtomicAction, Goal, ScreenState, VerificationResult};
-use crate::vision;
+use crate::vision::{self, VisionBackend};
+use crate::vision::diff::ScreenDiff;
 use regex::Regex;
-use tauri::AppHandle;
-use tokio::time::{sleep, Duration};
+use std::sync::Arc;
+use tauri::{AppHandle, Runtime};
 
 use super::observer::Observer;
 
+/// Changed regions listed in the verification prompt
+const MAX_LISTED_REGIONS: usize = 5;
+
 pub struct Verifier {
     observer: Observer,
+    vision: Arc<dyn VisionBackend>,
 }
 
 impl Verifier {
     pub fn new() -> Self {
+        Self::with_vision(vision::backend())
+    }
+
+    /// A verifier that asks `vision` about the screen
+    pub fn with_vision(vision: Arc<dyn VisionBackend>) -> Self {
         Self {
-            observer: Observer::new(),
+            observer: Observer::with_vision(vision.clone()),
+            vision,
         }
     }
 
-    /// Verify if the goal was achieved after executing an action
-    pub async fn verify(
+    /// Verify if the goal was achieved after executing an action: the vision
+    /// model looks at the screen after it, told what `diff` says changed
+    pub async fn verify<R: Runtime>(
         &self,
-        app_handle: &AppHandle,
+        app_handle: &AppHandle<R>,
         goal: &Goal,
         action: &AtomicAction,
-        before_screen: &ScreenState,
+        after_screen: &ScreenState,
+        diff: &ScreenDiff,
     ) -> Result<VerificationResult, String> {
-        // Wait briefly for UI to update
-        sleep(Duration::from_millis(300)).await;
-
-        // Observe new screen state
-        let after_screen = self.observer.observe(app_handle, &goal.description).await?;
-
-        // Use vision model to verify
-        let verification_response = vision::verify_goal_with_debug(
+        let screenshot = after_screen
+            .screenshot
+            .as_ref()
+            .ok_or_else(|| "Observation has no screenshot".to_string())?;
+        // The model sees screenshot pixels, the diff is in screen points
+        let changes = describe_changes(&diff.clone().scaled(1.0 / screenshot.scale_x, 1.0 / screenshot.scale_y));
+
+        let verification_response = vision::verify_goal_on_screen(
             app_handle,
+            self.vision.as_ref(),
+            &screenshot.png,
             &goal.description,
             &goal.success_criteria,
-            &before_screen.description,
-            &after_screen.description,
+            &changes,
         )
         .await?;
 
-        // Parse verification response
-        parse_verification_response(&verification_response, goal, action)
+        let mut result = parse_verification_response(&verification_response, goal, action)?;
+        // An action that changed nothing made no progress, whatever the model says
+        result.progress_made &= diff.changed();
+        Ok(result)
+    }
+
+    /// Quick verification based on screen change: any changed region, or
+    /// different perceptual hashes when the screenshots can't be compared
+    pub async fn quick_verify(&self, before: &ScreenState, after: &ScreenState) -> bool {
+        match self.observer.screen_changed(before, after).await {
+            Ok(diff) => diff.changed(),
+            Err(_) => before.screenshot_hash != after.screenshot_hash,
+        }
     }
+}
 
-    /// Quick verification based on screen change
-    pub fn quick_verify(&self, before_hash: &str, after_hash: &str) -> bool {
-        before_hash != after_hash
+/// What the last action changed, in words for the verification prompt
+fn describe_changes(diff: &ScreenDiff) -> String {
+    if !diff.changed() {
+        return "nothing visible changed".to_string();
     }
+    let regions: Vec<String> = diff
+        .regions
+        .iter()
+        .take(MAX_LISTED_REGIONS)
+        .map(|r| format!("({}, {}) to ({}, {})", r.x1, r.y1, r.x2, r.y2))
+        .collect();
+    format!(
+        "{:.0}% of the screen, in {} region(s): {}",
+        diff.score * 100.0,
+        diff.regions.len(),
+        regions.join(", ")
+    )
 }
 
 /// Parse the verification response from vision model
@@ -109,3 +148,36 @@ impl Default for Verifier {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ActionParams, ActionType};
+    use crate::test_support::replay;
+
+    #[tokio::test]
+    async fn test_verify_on_recorded_screen() {
+        let vision: Arc<dyn VisionBackend> = Arc::new(replay("safari"));
+        let app = tauri::test::mock_app();
+
+        // The recording never changes, so the click changed nothing
+        let observer = Observer::with_vision(vision.clone());
+        let before = observer.capture().unwrap();
+        let after = observer.capture().unwrap();
+        let diff = observer.screen_changed(&before, &after).await.unwrap();
+        assert!(!diff.changed());
+        assert_eq!(describe_changes(&diff), "nothing visible changed");
+
+        let goal = Goal::new("Open apple.com".into(), "The Apple homepage is showing".into());
+        let action = AtomicAction::new(
+            ActionType::MouseClick,
+            ActionParams::MouseClick { x: 1280, y: 104, button: None },
+            "Clicking the address bar".into(),
+        );
+        let result = Verifier::with_vision(vision).verify(app.handle(), &goal, &action, &after, &diff).await.unwrap();
+        assert!(!result.goal_achieved);
+        // The model saw progress, but nothing moved
+        assert!(!result.progress_made);
+        assert_eq!(result.observation, "Safari is open on the Start Page");
+    }
+}
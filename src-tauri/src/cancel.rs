@@ -0,0 +1,139 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Cancellation flag plus a wake-up for requests waiting on it
+pub struct CancelState {
+    cancelled: AtomicBool,
+    /// Wakes requests waiting in `cancellable`
+    notify: Notify,
+}
+
+/// Cancellation of the running task (agent session or plan execution).
+/// Only one task runs at a time.
+static STATE: CancelState = CancelState::new();
+
+/// Error text for work stopped by a cancel
+pub const CANCELLED_MESSAGE: &str = "Cancelled";
+
+/// The running task was cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl From<Cancelled> for String {
+    fn from(_: Cancelled) -> Self {
+        CANCELLED_MESSAGE.to_string()
+    }
+}
+
+impl CancelState {
+    pub const fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            notify: Notify::const_new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Sleep unless cancelled first
+    pub async fn sleep(&self, duration: Duration) -> Result<(), Cancelled> {
+        self.cancellable(async {
+            tokio::time::sleep(duration).await;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Run `future` unless cancelled first
+    pub async fn cancellable<T, E: From<Cancelled>>(&self, future: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        // Register before checking the flag so a cancel in between isn't missed
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+
+        tokio::select! {
+            result = future => result,
+            _ = notified => Err(Cancelled.into()),
+        }
+    }
+}
+
+impl Default for CancelState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cancel the running task, aborting any request in flight
+pub fn cancel() {
+    STATE.cancel();
+}
+
+/// Clear a previous cancel when a new task starts
+pub fn reset() {
+    STATE.reset();
+}
+
+pub fn is_cancelled() -> bool {
+    STATE.is_cancelled()
+}
+
+/// Sleep unless the task is cancelled first
+pub async fn sleep(duration: Duration) -> Result<(), Cancelled> {
+    STATE.sleep(duration).await
+}
+
+/// Run `future` unless the task is cancelled first. Dropping the future
+/// aborts its HTTP request, so a cancel takes effect at once.
+pub async fn cancellable<T, E: From<Cancelled>>(future: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    STATE.cancellable(future).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_aborts_pending_future() {
+        // A state of its own, so other tests' sleeps aren't cancelled
+        let state = std::sync::Arc::new(CancelState::new());
+        let pending = tokio::spawn({
+            let state = state.clone();
+            async move {
+                state
+                    .cancellable(async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        Ok::<_, String>(())
+                    })
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        state.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(1), pending).await.unwrap().unwrap();
+        assert_eq!(result, Err(CANCELLED_MESSAGE.to_string()));
+
+        // Still cancelled until the next task starts
+        assert!(state.cancellable(async { Ok::<_, String>(()) }).await.is_err());
+        state.reset();
+        assert!(state.cancellable(async { Ok::<_, String>(()) }).await.is_ok());
+        assert!(!is_cancelled());
+    }
+}
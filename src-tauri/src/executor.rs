@@ -1,35 +1,17 @@
+use crate::cancel;
 use crate::computer;
 use crate::types::{ActionParams, ActionPlan, MouseButton};
-use crate::vision;
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::vision::{self, VisionBackend};
 use tauri::{AppHandle, Emitter};
 use tokio::time::{sleep, Duration};
 
-static CANCELLED: AtomicBool = AtomicBool::new(false);
-
-pub fn cancel_execution() {
-    CANCELLED.store(true, Ordering::SeqCst);
-}
-
-fn reset_cancellation() {
-    CANCELLED.store(false, Ordering::SeqCst);
-}
-
-fn is_cancelled() -> bool {
-    CANCELLED.load(Ordering::SeqCst)
-}
-
 pub async fn execute_plan(app: &AppHandle, plan: &ActionPlan) -> Result<(), String> {
-    reset_cancellation();
+    cancel::reset();
+    let vision = vision::backend();
 
     for (index, step) in plan.steps.iter().enumerate() {
-        if is_cancelled() {
-            app.emit("execution_done", serde_json::json!({
-                "success": false,
-                "message": "Execution cancelled"
-            }))
-            .map_err(|e| e.to_string())?;
-            return Ok(());
+        if cancel::is_cancelled() {
+            return emit_cancelled(app);
         }
 
         // Get debug info about what will be executed
@@ -43,7 +25,7 @@ pub async fn execute_plan(app: &AppHandle, plan: &ActionPlan) -> Result<(), Stri
             .map_err(|e| e.to_string())?;
 
         // Execute the step
-        let result = execute_step(&step.params).await;
+        let result = execute_step(vision.as_ref(), &step.params).await;
 
         // Emit step completed
         let success = result.is_ok();
@@ -57,6 +39,10 @@ pub async fn execute_plan(app: &AppHandle, plan: &ActionPlan) -> Result<(), Stri
         .map_err(|e| e.to_string())?;
 
         if let Err(e) = result {
+            // A cancel aborts a find+click mid-request
+            if cancel::is_cancelled() {
+                return emit_cancelled(app);
+            }
             app.emit(
                 "execution_done",
                 serde_json::json!({
@@ -83,6 +69,14 @@ pub async fn execute_plan(app: &AppHandle, plan: &ActionPlan) -> Result<(), Stri
     Ok(())
 }
 
+fn emit_cancelled(app: &AppHandle) -> Result<(), String> {
+    app.emit("execution_done", serde_json::json!({
+        "success": false,
+        "message": "Execution cancelled"
+    }))
+    .map_err(|e| e.to_string())
+}
+
 fn get_debug_info(params: &ActionParams) -> String {
     match params {
         ActionParams::OpenApp { app_name } => format!("open -a {}", app_name),
@@ -99,10 +93,11 @@ fn get_debug_info(params: &ActionParams) -> String {
         ActionParams::MouseMove { x, y } => format!("move: ({}, {})", x, y),
         ActionParams::Wait { ms } => format!("wait: {}ms", ms),
         ActionParams::FindAndClick { element } => format!("find+click: {}", element),
+        ActionParams::FindImageAndClick { image_path } => format!("find+click image: {}", image_path),
     }
 }
 
-async fn execute_step(params: &ActionParams) -> Result<(), String> {
+async fn execute_step(vision: &dyn VisionBackend, params: &ActionParams) -> Result<(), String> {
     match params {
         ActionParams::OpenApp { app_name } => computer::open_app(app_name),
         ActionParams::TypeText { text } => computer::type_text(text),
@@ -118,13 +113,29 @@ async fn execute_step(params: &ActionParams) -> Result<(), String> {
             computer::mouse_click(*x, *y, btn)
         }
         ActionParams::MouseMove { x, y } => computer::mouse_move(*x, *y),
-        ActionParams::Wait { ms } => {
-            sleep(Duration::from_millis(*ms)).await;
-            Ok(())
-        }
+        ActionParams::Wait { ms } => cancel::sleep(Duration::from_millis(*ms)).await.map_err(String::from),
         ActionParams::FindAndClick { element } => {
-            let screen_element = vision::find_element(element).await?;
+            let screen_element = vision::locate_element(vision, element).await?;
+            computer::mouse_click(screen_element.x, screen_element.y, MouseButton::Left)
+        }
+        ActionParams::FindImageAndClick { image_path } => {
+            let screen_element = vision::locate_image(vision, image_path).await?;
             computer::mouse_click(screen_element.x, screen_element.y, MouseButton::Left)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::replay;
+
+    #[tokio::test]
+    async fn test_find_and_click_without_coordinates_fails() {
+        let params = ActionParams::FindAndClick {
+            element: "download button".into(),
+        };
+        let err = execute_step(&replay("safari"), &params).await.unwrap_err();
+        assert!(err.starts_with("Could not parse coordinates"), "{}", err);
+    }
+}
@@ -1,19 +1,26 @@
 mod agent;
+mod cancel;
 mod computer;
 mod executor;
 mod hotkey;
 mod llm;
 mod screenshot;
+mod settings;
+#[cfg(test)]
+mod test_support;
 mod types;
 mod vision;
 mod window;
 
 use agent::{Agent, AgentOrchestrator};
+use settings::Settings;
+use std::collections::HashMap;
 use tauri::{AppHandle, Emitter};
-use types::ActionPlan;
+use types::{ActionPlan, RepairStats};
 
 #[tauri::command]
 async fn plan_command(app: AppHandle, command: String) -> Result<(), String> {
+    cancel::reset();
     match llm::generate_plan(&command).await {
         Ok(plan) => {
             app.emit("plan_ready", &plan).map_err(|e| e.to_string())?;
@@ -34,7 +41,7 @@ async fn execute_plan(app: AppHandle, plan: ActionPlan) -> Result<(), String> {
 
 #[tauri::command]
 fn cancel_execution() {
-    executor::cancel_execution();
+    cancel::cancel();
 }
 
 #[tauri::command]
@@ -52,8 +59,7 @@ async fn start_agent(app: AppHandle, command: String) -> Result<(), String> {
 
 #[tauri::command]
 fn cancel_agent() {
-    agent::orchestrator::cancel_agent();
-    agent::runner::cancel();
+    cancel::cancel();
 }
 
 // === New Tool-based Agent ===
@@ -64,12 +70,53 @@ async fn start_agent_v2(app: AppHandle, command: String) -> Result<(), String> {
     agent.run().await.map(|_| ())
 }
 
+// === Settings ===
+
+#[tauri::command]
+fn get_settings() -> Settings {
+    settings::current()
+}
+
+#[tauri::command]
+fn update_settings(app: AppHandle, settings: Settings) -> Result<Settings, String> {
+    let settings = settings::update(settings)?;
+    start_preflight(&app);
+    Ok(settings)
+}
+
+#[tauri::command]
+fn reset_settings(app: AppHandle) -> Result<Settings, String> {
+    let settings = settings::reset()?;
+    start_preflight(&app);
+    Ok(settings)
+}
+
+/// How often each prompt's output needed repairing since launch
+#[tauri::command]
+fn get_repair_stats() -> HashMap<String, RepairStats> {
+    llm::repair::snapshot()
+}
+
+/// Check, pull and warm up the configured models in the background
+fn start_preflight(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        llm::preflight::run(&app).await;
+    });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .setup(|app| {
             hotkey::register_global_hotkey(app.handle())?;
+            // Load models, endpoints and limits (also picks the LLM backend)
+            if let Err(e) = settings::init(app.handle()) {
+                eprintln!("[SETUP] Warning: Failed to load settings: {}", e);
+            }
+            // Make sure the models exist and are loaded before the first command
+            start_preflight(app.handle());
             // Set up window to float above other apps (like Spotlight)
             if let Err(e) = window::setup_floating_window(app.handle()) {
                 eprintln!("[SETUP] Warning: Failed to setup floating window: {}", e);
@@ -83,7 +130,11 @@ pub fn run() {
             hide_window,
             start_agent,
             cancel_agent,
-            start_agent_v2
+            start_agent_v2,
+            get_settings,
+            update_settings,
+            reset_settings,
+            get_repair_stats
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
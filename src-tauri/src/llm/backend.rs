@@ -0,0 +1,440 @@
+use crate::types::{LlmCallType, LlmUsage};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::ollama::OllamaBackend;
+use super::openai::OpenAiBackend;
+use super::retry::Breaker;
+
+/// Sampling options shared by all backends.
+/// Unset fields fall back to the server's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Fixed seed for reproducible runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Context window size (Ollama only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    /// Maximum tokens to generate (`max_tokens` on OpenAI-compatible servers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+}
+
+/// A single text generation request, independent of the serving runtime
+#[derive(Debug, Clone)]
+pub struct GenerateRequest {
+    pub model: String,
+    pub system: Option<String>,
+    pub prompt: String,
+    pub call_type: LlmCallType,
+    pub options: GenerationOptions,
+    /// JSON Schema the output must follow
+    pub format: Option<serde_json::Value>,
+    /// GBNF grammar the output must follow (backends with `supports_grammar`)
+    pub grammar: Option<String>,
+}
+
+impl GenerateRequest {
+    pub fn new(model: &str, prompt: &str, call_type: LlmCallType) -> Self {
+        Self {
+            model: model.to_string(),
+            system: None,
+            prompt: prompt.to_string(),
+            call_type,
+            options: GenerationOptions::default(),
+            format: None,
+            grammar: None,
+        }
+    }
+}
+
+/// Who said a message in a chat conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    /// Result of a tool/action the assistant asked for
+    Tool,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+            ChatRole::Tool => "tool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Assistant, content: content.into() }
+    }
+
+    pub fn tool(content: impl Into<String>) -> Self {
+        Self { role: ChatRole::Tool, content: content.into() }
+    }
+}
+
+/// A multi-turn chat request
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub call_type: LlmCallType,
+    pub options: GenerationOptions,
+    /// JSON Schema the reply must follow
+    pub format: Option<serde_json::Value>,
+    /// GBNF grammar the reply must follow (backends with `supports_grammar`)
+    pub grammar: Option<String>,
+}
+
+impl ChatRequest {
+    pub fn new(model: &str, messages: Vec<ChatMessage>, call_type: LlmCallType) -> Self {
+        Self {
+            model: model.to_string(),
+            messages,
+            call_type,
+            options: GenerationOptions::default(),
+            format: None,
+            grammar: None,
+        }
+    }
+
+    /// Collapse the conversation into a single prompt for completion-only backends
+    pub fn to_generate_request(&self) -> GenerateRequest {
+        let system: Vec<&str> = self
+            .messages
+            .iter()
+            .filter(|m| m.role == ChatRole::System)
+            .map(|m| m.content.as_str())
+            .collect();
+
+        let mut prompt = String::new();
+        for message in self.messages.iter().filter(|m| m.role != ChatRole::System) {
+            let speaker = match message.role {
+                ChatRole::User => "USER",
+                ChatRole::Assistant => "ASSISTANT",
+                ChatRole::Tool => "RESULT",
+                ChatRole::System => continue,
+            };
+            prompt.push_str(&format!("{}: {}\n", speaker, message.content));
+        }
+        prompt.push_str("ASSISTANT:");
+
+        GenerateRequest {
+            model: self.model.clone(),
+            system: (!system.is_empty()).then(|| system.join("\n\n")),
+            prompt,
+            call_type: self.call_type,
+            options: self.options.clone(),
+            format: self.format.clone(),
+            grammar: self.grammar.clone(),
+        }
+    }
+}
+
+/// Text produced by a backend
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub text: String,
+    pub model: String,
+    /// Token counts and timings, if the server reported them
+    pub usage: Option<LlmUsage>,
+}
+
+/// Errors a backend can return
+#[derive(Debug, Clone)]
+pub enum LlmError {
+    /// The request did not finish within the client timeout
+    Timeout,
+    /// The server could not be reached
+    Connection(String),
+    /// The server answered with a non-success status
+    Http { status: u16, body: String },
+    /// The server answered but the body was not what we expected
+    InvalidResponse(String),
+    /// The model's output does not match the requested JSON Schema
+    SchemaViolation { target: &'static str, message: String },
+    /// Too many recent connection failures; calls fail fast for a while
+    CircuitOpen { retry_after_ms: u64 },
+    /// The user cancelled the task while the request was in flight
+    Cancelled,
+}
+
+impl fmt::Display for LlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmError::Timeout => write!(f, "LLM request timed out"),
+            LlmError::Connection(msg) => write!(f, "{}", msg),
+            LlmError::Http { status, body } => write!(f, "LLM server returned {}: {}", status, body),
+            LlmError::InvalidResponse(msg) => write!(f, "Invalid LLM response: {}", msg),
+            LlmError::SchemaViolation { target, message } => {
+                write!(f, "LLM output does not match the {} schema: {}", target, message)
+            }
+            LlmError::CircuitOpen { retry_after_ms } => write!(
+                f,
+                "LLM server unavailable after repeated failures; retrying in {}s",
+                retry_after_ms.div_ceil(1000)
+            ),
+            LlmError::Cancelled => write!(f, "{}", crate::cancel::CANCELLED_MESSAGE),
+        }
+    }
+}
+
+impl From<crate::cancel::Cancelled> for LlmError {
+    fn from(_: crate::cancel::Cancelled) -> Self {
+        LlmError::Cancelled
+    }
+}
+
+impl From<LlmError> for String {
+    fn from(e: LlmError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Receives each streamed chunk of text.
+/// Returning `false` stops generation early.
+pub type TokenSink<'a> = &'a mut (dyn FnMut(&str) -> bool + Send);
+
+/// A text LLM runtime (Ollama, a local OpenAI-compatible server, a mock, ...)
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    /// Short identifier used in logs and settings
+    fn name(&self) -> &'static str;
+
+    /// Circuit breaker for this backend's calls
+    fn breaker(&self) -> &Breaker;
+
+    /// Generate a completion for the prompt
+    async fn generate(&self, request: &GenerateRequest) -> Result<Generation, LlmError>;
+
+    /// Generate a completion, handing text to `on_token` as it arrives.
+    /// Backends without streaming deliver the whole response as one chunk.
+    async fn generate_stream(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let generation = self.generate(request).await?;
+        on_token(&generation.text);
+        Ok(generation)
+    }
+
+    /// Continue a multi-turn conversation, streaming like `generate_stream`.
+    /// Backends without a chat endpoint see the conversation as one prompt.
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        self.generate_stream(&request.to_generate_request(), on_token).await
+    }
+
+    /// Whether `grammar` in requests is enforced while decoding.
+    /// Other backends ignore it, so callers must still validate the output.
+    fn supports_grammar(&self) -> bool {
+        false
+    }
+
+    /// Whether the runtime is reachable
+    async fn health(&self) -> bool;
+
+    /// Models the runtime can serve
+    async fn list_models(&self) -> Result<Vec<String>, LlmError>;
+}
+
+static BACKEND: RwLock<Option<Arc<dyn LlmBackend>>> = RwLock::new(None);
+
+/// Get the active backend, defaulting to Ollama on localhost
+pub fn backend() -> Arc<dyn LlmBackend> {
+    if let Some(backend) = BACKEND.read().ok().and_then(|b| b.clone()) {
+        return backend;
+    }
+
+    let default: Arc<dyn LlmBackend> = Arc::new(OllamaBackend::default());
+    if let Ok(mut slot) = BACKEND.write() {
+        return slot.get_or_insert(default).clone();
+    }
+    default
+}
+
+/// Replace the active backend
+pub fn set_backend(backend: Arc<dyn LlmBackend>) {
+    println!("[LLM] Using backend: {}", backend.name());
+    if let Ok(mut slot) = BACKEND.write() {
+        *slot = Some(backend);
+    }
+}
+
+/// Which runtime to talk to and where
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackendConfig {
+    /// "ollama" or "openai" (llama.cpp-server, vLLM, ...)
+    pub kind: String,
+    pub base_url: Option<String>,
+    pub api_key: Option<String>,
+    /// Request timeout; unset keeps the backend's default
+    pub timeout_secs: Option<u64>,
+    /// How long Ollama keeps models loaded (e.g. "30m"); ignored by other backends
+    pub keep_alive: Option<String>,
+    /// GGUF file for the embedded backend
+    pub model_path: Option<String>,
+}
+
+/// Create a backend from its config
+pub fn create_backend(config: &BackendConfig) -> Result<Arc<dyn LlmBackend>, String> {
+    let base_url = config.base_url.as_deref().filter(|u| !u.trim().is_empty());
+    match config.kind.trim().to_lowercase().as_str() {
+        "" | "ollama" => {
+            let mut backend = match base_url {
+                Some(url) => OllamaBackend::new(url),
+                None => OllamaBackend::default(),
+            };
+            if let Some(secs) = config.timeout_secs {
+                backend = backend.with_timeout(Duration::from_secs(secs));
+            }
+            Ok(Arc::new(backend.with_keep_alive(config.keep_alive.clone())))
+        }
+        "openai" | "llama.cpp" | "vllm" => {
+            let mut backend = match base_url {
+                Some(url) => OpenAiBackend::new(url),
+                None => OpenAiBackend::default(),
+            };
+            if let Some(secs) = config.timeout_secs {
+                backend = backend.with_timeout(Duration::from_secs(secs));
+            }
+            // Only llama.cpp-server understands the `grammar` field
+            let grammar = config.kind.trim().eq_ignore_ascii_case("llama.cpp");
+            Ok(Arc::new(backend.with_api_key(config.api_key.clone()).with_grammar(grammar)))
+        }
+        #[cfg(feature = "embedded-llm")]
+        "embedded" => {
+            let path = config
+                .model_path
+                .as_deref()
+                .ok_or("The embedded backend needs a model path")?;
+            let backend = super::embedded::EmbeddedBackend::load(std::path::Path::new(path))?;
+            Ok(Arc::new(backend))
+        }
+        #[cfg(not(feature = "embedded-llm"))]
+        "embedded" => Err("Otto was built without the embedded-llm feature".to_string()),
+        other => Err(format!("Unknown LLM backend: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct MockBackend {
+        breaker: Breaker,
+    }
+
+    #[async_trait]
+    impl LlmBackend for MockBackend {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn breaker(&self) -> &Breaker {
+            &self.breaker
+        }
+
+        async fn generate(&self, request: &GenerateRequest) -> Result<Generation, LlmError> {
+            Ok(Generation {
+                text: format!("echo: {}", request.prompt),
+                model: request.model.clone(),
+                usage: None,
+            })
+        }
+
+        async fn health(&self) -> bool {
+            true
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+            Ok(vec!["mock-model".to_string()])
+        }
+    }
+
+    #[test]
+    fn test_chat_request_flattens_for_generate() {
+        let request = ChatRequest::new(
+            "m",
+            vec![
+                ChatMessage::system("Pick one action."),
+                ChatMessage::user("Goal: Open Safari"),
+                ChatMessage::assistant("open Safari"),
+                ChatMessage::tool("OK"),
+            ],
+            LlmCallType::ActionDecision,
+        );
+
+        let flat = request.to_generate_request();
+        assert_eq!(flat.system.as_deref(), Some("Pick one action."));
+        assert_eq!(
+            flat.prompt,
+            "USER: Goal: Open Safari\nASSISTANT: open Safari\nRESULT: OK\nASSISTANT:"
+        );
+    }
+
+    #[test]
+    fn test_create_backend() {
+        let config = |kind: &str, base_url: Option<&str>| BackendConfig {
+            kind: kind.to_string(),
+            base_url: base_url.map(|u| u.to_string()),
+            api_key: None,
+            timeout_secs: None,
+            keep_alive: None,
+            model_path: None,
+        };
+        assert_eq!(create_backend(&config("ollama", None)).unwrap().name(), "ollama");
+        assert_eq!(create_backend(&config(" Ollama ", None)).unwrap().name(), "ollama");
+        assert_eq!(create_backend(&config("vllm", Some("http://localhost:8000"))).unwrap().name(), "openai");
+        assert!(!create_backend(&config("vllm", None)).unwrap().supports_grammar());
+        assert!(create_backend(&config("llama.cpp", None)).unwrap().supports_grammar());
+        assert!(!create_backend(&config("ollama", None)).unwrap().supports_grammar());
+        assert!(create_backend(&config("nope", None)).is_err());
+        // No model file configured
+        assert!(create_backend(&config("embedded", None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_calls_go_to_the_given_backend() {
+        let backend = MockBackend::default();
+        let text = super::super::generate_with(&backend, "hi", LlmCallType::ActionDecision, None)
+            .await
+            .unwrap();
+        assert_eq!(text, "echo: hi");
+        assert_eq!(backend.list_models().await.unwrap(), vec!["mock-model"]);
+    }
+}
@@ -0,0 +1,330 @@
+use async_trait::async_trait;
+use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::json_schema_to_grammar;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::LlamaModel;
+use llama_cpp_2::sampling::LlamaSampler;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use super::backend::{
+    ChatMessage, ChatRequest, ChatRole, GenerateRequest, Generation, GenerationOptions, LlmBackend, LlmError,
+    TokenSink,
+};
+use super::retry::Breaker;
+use crate::types::LlmUsage;
+
+const DEFAULT_NUM_CTX: u32 = 4096;
+const DEFAULT_NUM_PREDICT: u32 = 512;
+/// Tokens decoded per batch while reading the prompt
+const BATCH_SIZE: usize = 512;
+
+/// llama.cpp may only be initialised once per process
+static LLAMA: OnceLock<Result<LlamaBackend, String>> = OnceLock::new();
+
+fn llama() -> Result<&'static LlamaBackend, LlmError> {
+    LLAMA
+        .get_or_init(|| LlamaBackend::init().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| LlmError::Connection(format!("Failed to start llama.cpp: {}", e)))
+}
+
+/// CPU inference on a local GGUF file via llama.cpp, for machines without Ollama.
+/// One model is loaded; the model named in each request is ignored.
+pub struct EmbeddedBackend {
+    model: Arc<LlamaModel>,
+    /// File name, reported as the model that answered
+    name: String,
+    breaker: Breaker,
+}
+
+impl EmbeddedBackend {
+    /// Load a GGUF model. Blocks while the file is mapped.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Err(format!(
+                "Embedded model not found at {}. Run scripts/download-model.sh and rebuild",
+                path.display()
+            ));
+        }
+
+        let backend = llama().map_err(String::from)?;
+        let started = Instant::now();
+        let model = LlamaModel::load_from_file(backend, path, &LlamaModelParams::default())
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "embedded".to_string());
+        println!("[LLM] Loaded embedded model {} in {}ms", name, started.elapsed().as_millis());
+
+        Ok(Self {
+            model: Arc::new(model),
+            name,
+            breaker: Breaker::new(),
+        })
+    }
+
+    /// Run generation on a blocking thread, forwarding tokens to `on_token`.
+    /// A JSON Schema `format` is enforced as a grammar unless `grammar` is set.
+    async fn run(
+        &self,
+        prompt: String,
+        options: GenerationOptions,
+        grammar: Option<String>,
+        format: Option<&serde_json::Value>,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let grammar = match (grammar, format) {
+            (Some(grammar), _) => Some(grammar),
+            (None, Some(schema)) => Some(
+                json_schema_to_grammar(&schema.to_string())
+                    .map_err(|e| LlmError::InvalidResponse(format!("Unsupported JSON Schema: {}", e)))?,
+            ),
+            (None, None) => None,
+        };
+        let model = self.model.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+
+        let worker = {
+            let stop = stop.clone();
+            tokio::task::spawn_blocking(move || {
+                generate_blocking(&model, &prompt, &options, grammar.as_deref(), &stop, tx)
+            })
+        };
+
+        let mut text = String::new();
+        while let Some(token) = rx.recv().await {
+            text.push_str(&token);
+            if !on_token(&token) {
+                println!("[LLM] Stopping generation early ({} chars)", text.len());
+                stop.store(true, Ordering::SeqCst);
+                break;
+            }
+        }
+
+        let usage = worker
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Embedded inference panicked: {}", e)))??;
+
+        Ok(Generation {
+            text,
+            model: self.name.clone(),
+            usage: Some(usage),
+        })
+    }
+}
+
+/// Decode the prompt, then sample until end of generation, a stop string,
+/// `num_predict` tokens or the caller asks to stop. With a grammar, only
+/// tokens that keep the output valid can be sampled.
+fn generate_blocking(
+    model: &LlamaModel,
+    prompt: &str,
+    options: &GenerationOptions,
+    grammar: Option<&str>,
+    stop: &AtomicBool,
+    tx: mpsc::UnboundedSender<String>,
+) -> Result<LlmUsage, LlmError> {
+    let started = Instant::now();
+    let failed = |what: &str, e: &dyn std::fmt::Display| LlmError::InvalidResponse(format!("{}: {}", what, e));
+
+    let num_ctx = options.num_ctx.unwrap_or(DEFAULT_NUM_CTX);
+    let context_params = LlamaContextParams::default().with_n_ctx(NonZeroU32::new(num_ctx));
+    let mut ctx = model
+        .new_context(llama()?, context_params)
+        .map_err(|e| failed("Failed to create context", &e))?;
+
+    // Parse special tokens so the ChatML markers aren't read as plain text
+    let vocab = model.vocab();
+    let tokens = vocab.tokenize(prompt.as_bytes(), true, true);
+    if tokens.is_empty() {
+        return Err(LlmError::InvalidResponse("Prompt is empty".into()));
+    }
+    let max_new = options.num_predict.unwrap_or(DEFAULT_NUM_PREDICT) as usize;
+    if tokens.len() + max_new > num_ctx as usize {
+        return Err(LlmError::InvalidResponse(format!(
+            "Prompt is {} tokens, too long for a {} token context",
+            tokens.len(),
+            num_ctx
+        )));
+    }
+
+    // Feed the prompt in chunks; only the last token needs logits
+    let mut batch = LlamaBatch::new(BATCH_SIZE, 1);
+    let last = tokens.len() - 1;
+    for (start, chunk) in tokens.chunks(BATCH_SIZE).enumerate().map(|(i, c)| (i * BATCH_SIZE, c)) {
+        batch.clear();
+        for (offset, token) in chunk.iter().enumerate() {
+            let pos = start + offset;
+            batch
+                .add(*token, pos as i32, &[0], pos == last)
+                .map_err(|e| failed("Failed to build batch", &e))?;
+        }
+        ctx.decode(&mut batch).map_err(|e| failed("Failed to decode prompt", &e))?;
+    }
+
+    let mut samplers = vec![];
+    if let Some(grammar) = grammar {
+        let sampler = LlamaSampler::grammar(model, grammar, "root").map_err(|e| failed("Invalid grammar", &e))?;
+        samplers.push(sampler);
+    }
+    match options.temperature {
+        Some(temperature) if temperature > 0.0 => {
+            samplers.push(LlamaSampler::temp(temperature));
+            samplers.push(LlamaSampler::dist(options.seed.unwrap_or(0) as u32));
+        }
+        _ => samplers.push(LlamaSampler::greedy()),
+    }
+    let mut sampler = LlamaSampler::chain_simple(samplers);
+
+    let stops = options.stop.clone().unwrap_or_default();
+    let mut text = String::new();
+    // Bytes of a character split across tokens
+    let mut pending: Vec<u8> = vec![];
+    let mut position = tokens.len() as i32;
+    let mut generated = 0;
+
+    while generated < max_new && !stop.load(Ordering::SeqCst) {
+        // Sampling also advances the grammar
+        let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+        if vocab.is_eog(token) {
+            break;
+        }
+        generated += 1;
+
+        pending.extend(vocab.token_to_piece(token, false, None));
+        let piece = match std::str::from_utf8(&pending) {
+            Ok(piece) => piece.to_string(),
+            // Wait for the rest of the character
+            Err(e) if e.error_len().is_none() => String::new(),
+            Err(_) => String::from_utf8_lossy(&pending).to_string(),
+        };
+        if !piece.is_empty() {
+            pending.clear();
+        }
+        text.push_str(&piece);
+
+        // Stop strings are not part of the output
+        if let Some(cut) = stops.iter().filter_map(|s| text.find(s.as_str())).min() {
+            let before = text.len() - piece.len();
+            if cut > before {
+                let _ = tx.send(text[before..cut].to_string());
+            }
+            break;
+        }
+        if tx.send(piece).is_err() {
+            break;
+        }
+
+        batch.clear();
+        batch
+            .add(token, position, &[0], true)
+            .map_err(|e| failed("Failed to build batch", &e))?;
+        position += 1;
+        ctx.decode(&mut batch).map_err(|e| failed("Failed to decode", &e))?;
+    }
+
+    Ok(LlmUsage {
+        prompt_tokens: tokens.len() as u64,
+        completion_tokens: generated as u64,
+        total_duration_ms: started.elapsed().as_millis() as u64,
+        load_duration_ms: 0,
+    })
+}
+
+/// Qwen's ChatML prompt format, ending with an open assistant turn
+fn chatml_prompt(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let (role, content) = match message.role {
+            ChatRole::Tool => ("user", format!("Result: {}", message.content)),
+            role => (role.as_str(), message.content.clone()),
+        };
+        prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, content));
+    }
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
+
+#[async_trait]
+impl LlmBackend for EmbeddedBackend {
+    fn name(&self) -> &'static str {
+        "embedded"
+    }
+
+    fn breaker(&self) -> &Breaker {
+        &self.breaker
+    }
+
+    async fn generate(&self, request: &GenerateRequest) -> Result<Generation, LlmError> {
+        self.generate_stream(request, &mut |_| true).await
+    }
+
+    async fn generate_stream(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let mut messages = vec![];
+        if let Some(system) = &request.system {
+            messages.push(ChatMessage::system(system));
+        }
+        messages.push(ChatMessage::user(&request.prompt));
+        let prompt = chatml_prompt(&messages);
+        self.run(prompt, request.options.clone(), request.grammar.clone(), request.format.as_ref(), on_token)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let prompt = chatml_prompt(&request.messages);
+        self.run(prompt, request.options.clone(), request.grammar.clone(), request.format.as_ref(), on_token)
+            .await
+    }
+
+    fn supports_grammar(&self) -> bool {
+        true
+    }
+
+    async fn health(&self) -> bool {
+        true
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        Ok(vec![self.name.clone()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chatml_prompt() {
+        let prompt = chatml_prompt(&[
+            ChatMessage::system("You control a Mac."),
+            ChatMessage::user("Goal: Open Safari"),
+            ChatMessage::assistant("open Safari"),
+            ChatMessage::tool("OK"),
+        ]);
+        assert_eq!(
+            prompt,
+            "<|im_start|>system\nYou control a Mac.<|im_end|>\n\
+             <|im_start|>user\nGoal: Open Safari<|im_end|>\n\
+             <|im_start|>assistant\nopen Safari<|im_end|>\n\
+             <|im_start|>user\nResult: OK<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use serde::de::DeserializeOwned;
+use std::fmt;
+
+/// A fix applied to model output to make it valid JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonRepair {
+    /// Wrapped in a ``` code fence
+    CodeFence,
+    /// Prose before or after the JSON
+    SurroundingText,
+    /// 'single' quoted strings
+    SingleQuotes,
+    /// `[1, 2,]` or `{"a": 1,}`
+    TrailingCommas,
+    /// `{tool: "wait"}`
+    UnquotedKeys,
+    /// A second JSON value after the first; only the first is kept
+    ExtraValues,
+}
+
+impl fmt::Display for JsonRepair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JsonRepair::CodeFence => "code fence",
+            JsonRepair::SurroundingText => "surrounding text",
+            JsonRepair::SingleQuotes => "single quotes",
+            JsonRepair::TrailingCommas => "trailing commas",
+            JsonRepair::UnquotedKeys => "unquoted keys",
+            JsonRepair::ExtraValues => "extra values",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The first JSON value in some model output, rewritten as strict JSON
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairedJson {
+    pub json: String,
+    /// Empty when the output was already clean
+    pub repairs: Vec<JsonRepair>,
+}
+
+impl RepairedJson {
+    /// For the debug overlay: the repairs made, then the JSON
+    pub fn describe(&self) -> String {
+        if self.repairs.is_empty() {
+            return self.json.clone();
+        }
+        let repairs: Vec<String> = self.repairs.iter().map(|r| r.to_string()).collect();
+        format!("Repaired: {}\n{}", repairs.join(", "), self.json)
+    }
+}
+
+/// Find the first JSON object or array in `text` and fix the mistakes small
+/// models make. None if there is no complete value.
+pub fn extract_json(text: &str) -> Option<RepairedJson> {
+    let mut repairs = vec![];
+
+    let mut body = text.trim();
+    if let Some(fenced) = strip_code_fence(body) {
+        repairs.push(JsonRepair::CodeFence);
+        body = fenced.trim();
+    }
+
+    let start = body.find(['{', '['])?;
+    if start > 0 {
+        repairs.push(JsonRepair::SurroundingText);
+    }
+
+    let (json, end) = rewrite_value(&body[start..], &mut repairs)?;
+
+    let rest = body[start + end..].trim();
+    if rest.starts_with(['{', '[']) {
+        repairs.push(JsonRepair::ExtraValues);
+    } else if !rest.is_empty() && !repairs.contains(&JsonRepair::SurroundingText) {
+        repairs.push(JsonRepair::SurroundingText);
+    }
+
+    Some(RepairedJson { json, repairs })
+}
+
+/// Parse `text` as `T`, repairing it first if it isn't valid as is.
+/// On failure the error is for the repaired text when there was one.
+pub fn parse_lenient<T: DeserializeOwned>(text: &str) -> Result<(T, Vec<JsonRepair>), serde_json::Error> {
+    let err = match serde_json::from_str::<T>(text.trim()) {
+        Ok(value) => return Ok((value, vec![])),
+        Err(e) => e,
+    };
+
+    match extract_json(text) {
+        Some(repaired) => serde_json::from_str::<T>(&repaired.json).map(|value| (value, repaired.repairs)),
+        None => Err(err),
+    }
+}
+
+/// Contents of the first ``` fence, without the language tag
+fn strip_code_fence(text: &str) -> Option<&str> {
+    let open = text.find("```")?;
+    let after = &text[open + 3..];
+    // Skip "json" or any other tag up to the end of the line
+    let body_start = match after.find('\n') {
+        Some(newline) if !after[..newline].contains(['{', '[']) => newline + 1,
+        _ => 0,
+    };
+    let body = &after[body_start..];
+    let close = body.find("```").unwrap_or(body.len());
+    Some(&body[..close])
+}
+
+/// Copy one JSON value starting at `text[0]` as strict JSON.
+/// Returns it and the number of bytes of `text` it used.
+fn rewrite_value(text: &str, repairs: &mut Vec<JsonRepair>) -> Option<(String, usize)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i].1;
+        match c {
+            '"' | '\'' => {
+                if c == '\'' {
+                    note(JsonRepair::SingleQuotes, repairs);
+                }
+                i = copy_string(&chars, i, &mut out)?;
+                continue;
+            }
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    out.push(c);
+                    let end = chars.get(i + 1).map(|(pos, _)| *pos).unwrap_or(text.len());
+                    return Some((out, end));
+                }
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|(_, c)| !c.is_whitespace()).map(|(_, c)| *c);
+                if matches!(next, Some('}') | Some(']')) {
+                    note(JsonRepair::TrailingCommas, repairs);
+                    i += 1;
+                    continue;
+                }
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let word_end = chars[i..]
+                    .iter()
+                    .position(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '$'))
+                    .map(|n| i + n)
+                    .unwrap_or(chars.len());
+                let word: String = chars[i..word_end].iter().map(|(_, c)| c).collect();
+                let next = chars[word_end..].iter().find(|(_, c)| !c.is_whitespace()).map(|(_, c)| *c);
+                if next == Some(':') {
+                    note(JsonRepair::UnquotedKeys, repairs);
+                    out.push_str(&serde_json::to_string(&word).ok()?);
+                } else {
+                    // true, false, null (or something the parser will reject)
+                    out.push_str(&word);
+                }
+                i = word_end;
+                continue;
+            }
+            _ => {}
+        }
+        out.push(c);
+        i += 1;
+    }
+
+    // Ran out of text before the value was closed
+    None
+}
+
+fn note(repair: JsonRepair, repairs: &mut Vec<JsonRepair>) {
+    if !repairs.contains(&repair) {
+        repairs.push(repair);
+    }
+}
+
+/// Copy the string starting at `chars[start]` (either quote style) as a
+/// double-quoted JSON string. Returns the index just past its closing quote.
+fn copy_string(chars: &[(usize, char)], start: usize, out: &mut String) -> Option<usize> {
+    let quote = chars[start].1;
+    out.push('"');
+    let mut i = start + 1;
+    while i < chars.len() {
+        let c = chars[i].1;
+        match c {
+            '\\' => {
+                let next = chars.get(i + 1)?.1;
+                // \' is not a JSON escape
+                if next == '\'' {
+                    out.push('\'');
+                } else {
+                    out.push('\\');
+                    out.push(next);
+                }
+                i += 2;
+                continue;
+            }
+            c if c == quote => {
+                out.push('"');
+                return Some(i + 1);
+            }
+            '"' => out.push_str("\\\""),
+            // Raw control characters are not allowed in JSON strings
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn repair(text: &str) -> (serde_json::Value, Vec<JsonRepair>) {
+        let repaired = extract_json(text).unwrap();
+        (serde_json::from_str(&repaired.json).unwrap(), repaired.repairs)
+    }
+
+    #[test]
+    fn test_clean_json_is_untouched() {
+        let (value, repairs) = repair(r#"{"tool": "type", "params": {"text": "a, b}"}}"#);
+        assert_eq!(value, json!({"tool": "type", "params": {"text": "a, b}"}}));
+        assert!(repairs.is_empty());
+    }
+
+    #[test]
+    fn test_small_model_mistakes() {
+        let (value, repairs) = repair("```json\n{tools: [{'tool': 'key', 'params': {'key': 'l', 'modifiers': ['cmd',],}},]}\n```");
+        assert_eq!(value, json!({"tools": [{"tool": "key", "params": {"key": "l", "modifiers": ["cmd"]}}]}));
+        assert_eq!(
+            repairs,
+            vec![
+                JsonRepair::CodeFence,
+                JsonRepair::UnquotedKeys,
+                JsonRepair::SingleQuotes,
+                JsonRepair::TrailingCommas
+            ]
+        );
+
+        let (value, repairs) = repair(r#"Sure! {"tool": "wait", "params": {"ms": 300}} {"tool": "step_done"}"#);
+        assert_eq!(value, json!({"tool": "wait", "params": {"ms": 300}}));
+        assert_eq!(repairs, vec![JsonRepair::SurroundingText, JsonRepair::ExtraValues]);
+
+        let (value, _) = repair(r#"{'text': 'say "hi"', "done": true, 'it\'s': null}"#);
+        assert_eq!(value, json!({"text": "say \"hi\"", "done": true, "it's": null}));
+    }
+
+    #[test]
+    fn test_control_characters_in_strings() {
+        let (value, _) = repair("{'text': 'a\tb\r\nc\u{1}'}");
+        assert_eq!(value, json!({"text": "a\tb\r\nc\u{1}"}));
+
+        let (value, _) = repair("{\"text\": \"a\tb\"}");
+        assert_eq!(value, json!({"text": "a\tb"}));
+    }
+
+    #[test]
+    fn test_incomplete_json() {
+        assert!(extract_json(r#"{"tool": "open_app", "params": {"name": "Saf"#).is_none());
+        assert!(extract_json("open Safari").is_none());
+    }
+
+    #[test]
+    fn test_describe_lists_repairs() {
+        let repaired = extract_json("```\n[1, 2,]\n```").unwrap();
+        assert_eq!(repaired.describe(), "Repaired: code fence, trailing commas\n[1, 2]");
+    }
+}
@@ -0,0 +1,614 @@
+pub mod backend;
+#[cfg(feature = "embedded-llm")]
+pub mod embedded;
+pub mod json_repair;
+pub mod ollama;
+pub mod openai;
+pub mod preflight;
+pub mod repair;
+pub mod retry;
+pub mod schema;
+pub mod usage;
+
+pub use backend::{
+    backend, create_backend, set_backend, BackendConfig, ChatMessage, ChatRequest, GenerateRequest, GenerationOptions,
+    LlmBackend, LlmError,
+};
+pub use retry::RetryPolicy;
+pub use schema::{parse_structured, schema_for};
+
+use crate::cancel::cancellable;
+use crate::settings;
+use crate::types::{ActionParams, ActionPlan, ActionStep, ActionType, LlmCallType, LlmDebugEvent, LlmResponseEvent, LlmTokenEvent};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
+
+#[derive(Deserialize, JsonSchema)]
+struct LlmPlanResponse {
+    steps: Vec<LlmStep>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LlmStep {
+    #[serde(rename = "type")]
+    step_type: String,
+    description: String,
+    params: serde_json::Value,
+}
+
+fn build_prompt(command: &str) -> String {
+    format!(
+        r#"Convert the command to JSON steps. Available actions: open_app, type_text, press_key, wait.
+For browser search: open app, wait, press Cmd+L, type query, press return.
+
+Command: open safari
+Response: {{"steps":[{{"type":"open_app","description":"Open Safari","params":{{"app_name":"Safari"}}}}]}}
+
+Command: open safari and search hello
+Response: {{"steps":[{{"type":"open_app","description":"Open Safari","params":{{"app_name":"Safari"}}}},{{"type":"wait","description":"Wait","params":{{"ms":1000}}}},{{"type":"press_key","description":"Focus URL bar","params":{{"key":"l","modifiers":["cmd"]}}}},{{"type":"wait","description":"Wait","params":{{"ms":200}}}},{{"type":"type_text","description":"Type query","params":{{"text":"hello"}}}},{{"type":"press_key","description":"Search","params":{{"key":"return","modifiers":[]}}}}]}}
+
+Command: {}
+Response: "#,
+        command
+    )
+}
+
+/// Raw LLM call with debug event emission
+pub async fn call_ollama_with_debug(
+    app_handle: &AppHandle,
+    prompt: &str,
+    call_type: LlmCallType,
+) -> Result<String, String> {
+    call_ollama_streaming(app_handle, prompt, call_type, |_| false).await
+}
+
+/// Streaming LLM call with debug event emission.
+/// Each chunk is emitted as an `llm_token` event; generation stops as soon as
+/// `is_complete` returns true for the text received so far.
+pub async fn call_ollama_streaming(
+    app_handle: &AppHandle,
+    prompt: &str,
+    call_type: LlmCallType,
+    is_complete: impl Fn(&str) -> bool + Send + Sync,
+) -> Result<String, String> {
+    stream_with_debug(app_handle, LlmInput::Prompt(prompt), call_type, None, None, is_complete).await
+}
+
+/// LLM call whose output is constrained to `schema` (see `schema_for`).
+/// Parse the result with `parse_structured`.
+pub async fn call_with_schema(
+    app_handle: &AppHandle,
+    prompt: &str,
+    call_type: LlmCallType,
+    schema: serde_json::Value,
+) -> Result<String, String> {
+    stream_with_debug(app_handle, LlmInput::Prompt(prompt), call_type, Some(schema), None, has_complete_json).await
+}
+
+/// Multi-turn chat call with the same debug and streaming behaviour as
+/// `call_ollama_streaming`. Returns the assistant's reply.
+pub async fn call_chat_streaming(
+    app_handle: &AppHandle,
+    messages: &[ChatMessage],
+    call_type: LlmCallType,
+    is_complete: impl Fn(&str) -> bool + Send + Sync,
+) -> Result<String, String> {
+    stream_with_debug(app_handle, LlmInput::Chat(messages), call_type, None, None, is_complete).await
+}
+
+/// Chat call whose reply is constrained to a GBNF `grammar` on backends that
+/// support it. Other backends answer unconstrained, so parse the reply as usual.
+pub async fn call_chat_with_grammar(
+    app_handle: &AppHandle,
+    messages: &[ChatMessage],
+    call_type: LlmCallType,
+    grammar: &str,
+) -> Result<String, String> {
+    stream_with_debug(app_handle, LlmInput::Chat(messages), call_type, None, Some(grammar), |_| false).await
+}
+
+/// Chat call whose reply is constrained to `schema` (see `schema_for`).
+/// Streaming stops once the JSON value is complete.
+pub async fn call_chat_with_schema(
+    app_handle: &AppHandle,
+    messages: &[ChatMessage],
+    call_type: LlmCallType,
+    schema: serde_json::Value,
+) -> Result<String, String> {
+    stream_with_debug(app_handle, LlmInput::Chat(messages), call_type, Some(schema), None, has_complete_json).await
+}
+
+/// What is sent to the model: a single prompt or a conversation
+enum LlmInput<'a> {
+    Prompt(&'a str),
+    Chat(&'a [ChatMessage]),
+}
+
+impl LlmInput<'_> {
+    /// Text shown in the debug overlay
+    fn display(&self) -> String {
+        match self {
+            LlmInput::Prompt(prompt) => prompt.to_string(),
+            LlmInput::Chat(messages) => messages
+                .iter()
+                .map(|m| format!("[{}]\n{}", m.role.as_str(), m.content))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        }
+    }
+}
+
+async fn stream_with_debug(
+    app_handle: &AppHandle,
+    input: LlmInput<'_>,
+    call_type: LlmCallType,
+    format: Option<serde_json::Value>,
+    grammar: Option<&str>,
+    is_complete: impl Fn(&str) -> bool + Send + Sync,
+) -> Result<String, String> {
+    let call_id = Uuid::new_v4().to_string();
+    let settings = settings::current();
+    let route = settings.llm_route(call_type);
+    let model = route.model.clone();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+
+    // Emit prompt event
+    let prompt_event = LlmDebugEvent {
+        call_id: call_id.clone(),
+        call_type,
+        model: model.clone(),
+        prompt: input.display(),
+        timestamp,
+    };
+    let _ = app_handle.emit("llm_prompt", &prompt_event);
+
+    // Call LLM and measure time, retrying and falling back as configured
+    let start = Instant::now();
+    let backend = backend();
+    let grammar = grammar.filter(|_| backend.supports_grammar()).map(String::from);
+    let mut attempts =
+        retry::Attempts::new(&route.model, &route.fallback_models, settings.retry.clone(), backend.breaker());
+    let result = loop {
+        // Stream tokens to the overlay while the model generates.
+        // Index 0 tells the overlay to drop text from a failed attempt.
+        let mut received = String::new();
+        let mut index = 0;
+        let mut on_token = |token: &str| {
+            received.push_str(token);
+            let _ = app_handle.emit(
+                "llm_token",
+                &LlmTokenEvent {
+                    call_id: call_id.clone(),
+                    token: token.to_string(),
+                    index,
+                },
+            );
+            index += 1;
+            !is_complete(&received)
+        };
+
+        let result = match attempts.check_breaker() {
+            Err(e) => Err(e),
+            Ok(()) => match &input {
+                LlmInput::Prompt(prompt) => {
+                    let mut request = GenerateRequest::new(attempts.model(), prompt, call_type);
+                    request.options = route.options.clone();
+                    request.format = format.clone();
+                    request.grammar = grammar.clone();
+                    cancellable(backend.generate_stream(&request, &mut on_token)).await
+                }
+                LlmInput::Chat(messages) => {
+                    let mut request = ChatRequest::new(attempts.model(), messages.to_vec(), call_type);
+                    request.options = route.options.clone();
+                    request.format = format.clone();
+                    request.grammar = grammar.clone();
+                    cancellable(backend.chat_stream(&request, &mut on_token)).await
+                }
+            },
+        };
+        if !attempts.should_retry(&result).await {
+            break result;
+        }
+    };
+    let duration_ms = start.elapsed().as_millis() as u64;
+    let final_model = Some(attempts.model().to_string()).filter(|m| *m != model);
+    let attempts = attempts.into_log();
+    let usage = result.as_ref().ok().and_then(|g| g.usage);
+    usage::record(call_type, duration_ms, usage.as_ref());
+    let result = result.map(|g| g.text).map_err(String::from);
+
+    // Emit response event
+    let response_event = match &result {
+        Ok(response) => LlmResponseEvent {
+            call_id,
+            raw_response: response.clone(),
+            // Schema calls: the JSON that will be parsed and any repairs it needed
+            parsed_result: format
+                .as_ref()
+                .and_then(|_| json_repair::extract_json(response))
+                .map(|repaired| repaired.describe()),
+            duration_ms,
+            success: true,
+            error: None,
+            model: final_model,
+            attempts,
+            usage,
+        },
+        Err(e) => LlmResponseEvent {
+            call_id,
+            raw_response: String::new(),
+            parsed_result: None,
+            duration_ms,
+            success: false,
+            error: Some(e.clone()),
+            model: final_model,
+            attempts,
+            usage,
+        },
+    };
+    let _ = app_handle.emit("llm_response", &response_event);
+
+    result
+}
+
+/// Send a prompt to the active backend, optionally constrained to a JSON Schema
+async fn call_ollama(
+    prompt: &str,
+    call_type: LlmCallType,
+    format: Option<serde_json::Value>,
+) -> Result<String, String> {
+    generate_with(backend().as_ref(), prompt, call_type, format).await
+}
+
+/// Send a prompt to `backend`, with the routing, retries and usage
+/// accounting of every call
+async fn generate_with(
+    backend: &dyn LlmBackend,
+    prompt: &str,
+    call_type: LlmCallType,
+    format: Option<serde_json::Value>,
+) -> Result<String, String> {
+    let settings = settings::current();
+    let route = settings.llm_route(call_type);
+    let mut attempts = retry::Attempts::new(&route.model, &route.fallback_models, settings.retry, backend.breaker());
+
+    let start = Instant::now();
+    let result = loop {
+        let result = match attempts.check_breaker() {
+            Err(e) => Err(e),
+            Ok(()) => {
+                let mut request = GenerateRequest::new(attempts.model(), prompt, call_type);
+                request.options = route.options.clone();
+                request.format = format.clone();
+                cancellable(backend.generate(&request)).await
+            }
+        };
+        if !attempts.should_retry(&result).await {
+            break result;
+        }
+    };
+
+    let duration_ms = start.elapsed().as_millis() as u64;
+    usage::record(call_type, duration_ms, result.as_ref().ok().and_then(|g| g.usage.as_ref()));
+
+    Ok(result?.text)
+}
+
+/// Byte offset just past the first complete JSON object or array in `text`.
+/// Used to stop streaming once the model has produced what we parse.
+pub fn json_value_end(text: &str) -> Option<usize> {
+    let start = text.find(['{', '['])?;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Whether `text` already contains a complete JSON object or array
+pub fn has_complete_json(text: &str) -> bool {
+    json_value_end(text).is_some()
+}
+
+fn parse_step(step: &LlmStep) -> Result<ActionStep, String> {
+    let action_type = match step.step_type.as_str() {
+        "open_app" => ActionType::OpenApp,
+        "type_text" => ActionType::TypeText,
+        "press_key" => ActionType::PressKey,
+        "mouse_click" => ActionType::MouseClick,
+        "mouse_move" => ActionType::MouseMove,
+        "wait" => ActionType::Wait,
+        "find_and_click" => ActionType::FindAndClick,
+        "find_image_and_click" => ActionType::FindImageAndClick,
+        other => return Err(format!("Unknown action type: {}", other)),
+    };
+
+    let params = match step.step_type.as_str() {
+        "open_app" => {
+            let app_name = step.params["app_name"]
+                .as_str()
+                .ok_or("Missing app_name")?
+                .to_string();
+            ActionParams::OpenApp { app_name }
+        }
+        "type_text" => {
+            let text = step.params["text"]
+                .as_str()
+                .ok_or("Missing text")?
+                .to_string();
+            ActionParams::TypeText { text }
+        }
+        "press_key" => {
+            let key = step.params["key"]
+                .as_str()
+                .ok_or("Missing key")?
+                .to_string();
+            let modifiers = step.params["modifiers"]
+                .as_array()
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect()
+                });
+            ActionParams::PressKey { key, modifiers }
+        }
+        "mouse_click" => {
+            let x = step.params["x"].as_i64().ok_or("Missing x")? as i32;
+            let y = step.params["y"].as_i64().ok_or("Missing y")? as i32;
+            let button = step.params["button"].as_str().map(|s| s.to_string());
+            ActionParams::MouseClick { x, y, button }
+        }
+        "mouse_move" => {
+            let x = step.params["x"].as_i64().ok_or("Missing x")? as i32;
+            let y = step.params["y"].as_i64().ok_or("Missing y")? as i32;
+            ActionParams::MouseMove { x, y }
+        }
+        "wait" => {
+            let ms = step.params["ms"].as_u64().ok_or("Missing ms")?;
+            ActionParams::Wait { ms }
+        }
+        "find_and_click" => {
+            let element = step.params["element"]
+                .as_str()
+                .ok_or("Missing element")?
+                .to_string();
+            ActionParams::FindAndClick { element }
+        }
+        "find_image_and_click" => {
+            let image_path = step.params["image_path"]
+                .as_str()
+                .ok_or("Missing image_path")?
+                .to_string();
+            ActionParams::FindImageAndClick { image_path }
+        }
+        _ => return Err("Invalid step type".to_string()),
+    };
+
+    Ok(ActionStep {
+        id: Uuid::new_v4().to_string(),
+        action_type,
+        description: step.description.clone(),
+        params,
+    })
+}
+
+// Try to parse common command patterns directly without LLM
+fn try_parse_direct(command: &str) -> Option<Vec<ActionStep>> {
+    let cmd = command.to_lowercase();
+
+    // Pattern: "search X in safari" or "safari에서 X 검색"
+    if let Some(caps) = regex::Regex::new(r"(?i)search\s+(.+?)\s+in\s+(\w+)").ok()?.captures(&cmd) {
+        let query = caps.get(1)?.as_str().trim();
+        let app = caps.get(2)?.as_str().trim();
+        return Some(build_search_steps(app, query));
+    }
+
+    // Pattern: "open safari and search X"
+    if let Some(caps) = regex::Regex::new(r"(?i)open\s+(\w+)\s+and\s+search\s+(.+)").ok()?.captures(&cmd) {
+        let app = caps.get(1)?.as_str().trim();
+        let query = caps.get(2)?.as_str().trim();
+        return Some(build_search_steps(app, query));
+    }
+
+    // Pattern: "open X"
+    if let Some(caps) = regex::Regex::new(r"(?i)^open\s+(\w+)$").ok()?.captures(&cmd) {
+        let app = caps.get(1)?.as_str().trim();
+        return Some(vec![
+            ActionStep {
+                id: Uuid::new_v4().to_string(),
+                action_type: ActionType::OpenApp,
+                description: format!("Open {}", capitalize(app)),
+                params: ActionParams::OpenApp { app_name: capitalize(app) },
+            }
+        ]);
+    }
+
+    // Pattern: "click /path/to/icon.png" - match the image itself (path keeps its case, may have spaces or quotes)
+    if let Some(caps) = regex::Regex::new(r#"(?i)^click\s+(?:on\s+)?["']?(.+?\.png)["']?$"#).ok()?.captures(command.trim()) {
+        let image_path = caps.get(1)?.as_str();
+        return Some(vec![
+            ActionStep {
+                id: Uuid::new_v4().to_string(),
+                action_type: ActionType::FindImageAndClick,
+                description: format!("Find and click image: {}", image_path),
+                params: ActionParams::FindImageAndClick { image_path: image_path.to_string() },
+            }
+        ]);
+    }
+
+    // Pattern: "click on X" or "click the X"
+    if let Some(caps) = regex::Regex::new(r"(?i)click\s+(?:on\s+)?(?:the\s+)?(.+)").ok()?.captures(&cmd) {
+        let element = caps.get(1)?.as_str().trim();
+        return Some(vec![
+            ActionStep {
+                id: Uuid::new_v4().to_string(),
+                action_type: ActionType::FindAndClick,
+                description: format!("Find and click: {}", element),
+                params: ActionParams::FindAndClick { element: element.to_string() },
+            }
+        ]);
+    }
+
+    // Pattern: "find and click X"
+    if let Some(caps) = regex::Regex::new(r"(?i)find\s+and\s+click\s+(.+)").ok()?.captures(&cmd) {
+        let element = caps.get(1)?.as_str().trim();
+        return Some(vec![
+            ActionStep {
+                id: Uuid::new_v4().to_string(),
+                action_type: ActionType::FindAndClick,
+                description: format!("Find and click: {}", element),
+                params: ActionParams::FindAndClick { element: element.to_string() },
+            }
+        ]);
+    }
+
+    // Pattern: "type X"
+    if let Some(caps) = regex::Regex::new(r"(?i)^type\s+(.+)$").ok()?.captures(&cmd) {
+        let text = caps.get(1)?.as_str().trim();
+        return Some(vec![
+            ActionStep {
+                id: Uuid::new_v4().to_string(),
+                action_type: ActionType::TypeText,
+                description: format!("Type: {}", text),
+                params: ActionParams::TypeText { text: text.to_string() },
+            }
+        ]);
+    }
+
+    None
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
+
+fn build_search_steps(app: &str, query: &str) -> Vec<ActionStep> {
+    vec![
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::OpenApp,
+            description: format!("Open {}", capitalize(app)),
+            params: ActionParams::OpenApp { app_name: capitalize(app) },
+        },
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::Wait,
+            description: "Wait for app".to_string(),
+            params: ActionParams::Wait { ms: 1000 },
+        },
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::PressKey,
+            description: "Focus URL bar (Cmd+L)".to_string(),
+            params: ActionParams::PressKey { key: "l".to_string(), modifiers: Some(vec!["cmd".to_string()]) },
+        },
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::Wait,
+            description: "Wait".to_string(),
+            params: ActionParams::Wait { ms: 200 },
+        },
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::TypeText,
+            description: format!("Type: {}", query),
+            params: ActionParams::TypeText { text: query.to_string() },
+        },
+        ActionStep {
+            id: Uuid::new_v4().to_string(),
+            action_type: ActionType::PressKey,
+            description: "Search".to_string(),
+            params: ActionParams::PressKey { key: "return".to_string(), modifiers: Some(vec![]) },
+        },
+    ]
+}
+
+pub async fn generate_plan(command: &str) -> Result<ActionPlan, String> {
+    // Try direct parsing first (fast path)
+    if let Some(steps) = try_parse_direct(command) {
+        return Ok(ActionPlan {
+            id: Uuid::new_v4().to_string(),
+            original_command: command.to_string(),
+            steps,
+            requires_confirmation: false,
+        });
+    }
+
+    // Fall back to LLM for complex commands
+    let prompt = build_prompt(command);
+    let schema = schema_for::<LlmPlanResponse>();
+    let response = call_ollama(&prompt, LlmCallType::Decomposition, Some(schema)).await?;
+
+    let llm_plan: LlmPlanResponse = parse_structured(&response)?;
+
+    let steps: Result<Vec<ActionStep>, String> = llm_plan.steps.iter().map(parse_step).collect();
+
+    Ok(ActionPlan {
+        id: Uuid::new_v4().to_string(),
+        original_command: command.to_string(),
+        steps: steps?,
+        requires_confirmation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direct_image_click() {
+        let steps = try_parse_direct("Click on '~/My Icons/Save.png'").unwrap();
+        match &steps[0].params {
+            ActionParams::FindImageAndClick { image_path } => assert_eq!(image_path, "~/My Icons/Save.png"),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_value_end() {
+        assert_eq!(json_value_end(r#"{"tool": "step_done"}"#), Some(21));
+        assert_eq!(json_value_end(r#"JSON: {"tool": "type", "params": {"text": "a}b"}} more"#), Some(49));
+        assert_eq!(
+            json_repair::extract_json(r#"JSON: {"steps": []} then {"x": 1}"#).map(|r| r.json),
+            Some(r#"{"steps": []}"#.to_string())
+        );
+        assert_eq!(json_value_end(r#"[{"tool": "wait"}, {"tool": "key""#), None);
+        assert!(has_complete_json(r#"[{"tool": "wait", "params": {"ms": 500}}]"#));
+        assert!(!has_complete_json(r#"{"tool": "open_app", "params": {"name": "Saf"#));
+        assert!(!has_complete_json("open Safari"));
+    }
+}
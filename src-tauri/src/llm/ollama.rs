@@ -0,0 +1,410 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::backend::{
+    ChatMessage, ChatRequest, GenerateRequest, Generation, GenerationOptions, LlmBackend, LlmError, TokenSink,
+};
+use super::retry::Breaker;
+use super::usage::{self, OllamaMetrics};
+use crate::types::LlmUsage;
+
+const OLLAMA_TIMEOUT_SECS: u64 = 120;
+/// Downloads can take a long time on slow connections
+const PULL_TIMEOUT_SECS: u64 = 4 * 60 * 60;
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    stream: bool,
+    options: &'a GenerationOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+    options: &'a GenerationOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct PullRequest<'a> {
+    model: &'a str,
+    stream: bool,
+}
+
+/// One NDJSON line of a streamed `/api/pull` response
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    response: String,
+    #[serde(flatten)]
+    metrics: OllamaMetrics,
+}
+
+/// One NDJSON line of a streamed `/api/generate` or `/api/chat` response
+#[derive(Deserialize)]
+struct OllamaStreamChunk {
+    #[serde(default)]
+    response: String,
+    message: Option<OllamaChatMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+    /// Only present on the final chunk
+    #[serde(flatten)]
+    metrics: OllamaMetrics,
+}
+
+#[derive(Deserialize)]
+struct OllamaChatMessage {
+    #[serde(default)]
+    content: String,
+}
+
+impl OllamaStreamChunk {
+    fn text(&self) -> &str {
+        match &self.message {
+            Some(message) => &message.content,
+            None => &self.response,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<TagsModel>,
+}
+
+#[derive(Deserialize)]
+struct TagsModel {
+    name: String,
+}
+
+/// Ollama's native `/api/generate` and `/api/chat` endpoints
+pub struct OllamaBackend {
+    base_url: String,
+    client: reqwest::Client,
+    /// How long Ollama keeps the model loaded after a request
+    keep_alive: Option<String>,
+    breaker: Breaker,
+}
+
+impl OllamaBackend {
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(OLLAMA_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            keep_alive: None,
+            breaker: Breaker::new(),
+        }
+    }
+
+    /// Replace the default request timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        self
+    }
+
+    /// Send `keep_alive` (e.g. "30m") with every request
+    pub fn with_keep_alive(mut self, keep_alive: Option<String>) -> Self {
+        self.keep_alive = keep_alive.filter(|k| !k.trim().is_empty());
+        self
+    }
+
+    /// Download a model, reporting each progress line
+    pub async fn pull(
+        &self,
+        model: &str,
+        on_progress: &mut (dyn FnMut(&PullProgress) + Send),
+    ) -> Result<(), LlmError> {
+        let response = self
+            .client
+            .post(self.url("/api/pull"))
+            .timeout(Duration::from_secs(PULL_TIMEOUT_SECS))
+            .json(&PullRequest { model, stream: true })
+            .send()
+            .await
+            .map_err(|e| send_error(e, "Ollama"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LlmError::Http { status: status.as_u16(), body });
+        }
+
+        read_lines(response, |progress: PullProgress| {
+            if let Some(error) = &progress.error {
+                return Err(LlmError::InvalidResponse(format!("Pulling {} failed: {}", model, error)));
+            }
+            on_progress(&progress);
+            Ok(progress.status != "success")
+        })
+        .await
+    }
+
+    /// Load a model into memory without generating anything
+    pub async fn warm_up(&self, model: &str) -> Result<Option<LlmUsage>, LlmError> {
+        let options = GenerationOptions::default();
+        let body = OllamaRequest {
+            model,
+            prompt: "",
+            system: None,
+            stream: false,
+            options: &options,
+            format: None,
+            keep_alive: self.keep_alive.as_deref(),
+        };
+        let response = self.post("/api/generate", &body).await?;
+        let ollama_response: OllamaResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse Ollama response: {}", e)))?;
+        Ok(ollama_response.metrics.usage())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn post_generate(&self, request: &GenerateRequest, stream: bool) -> Result<reqwest::Response, LlmError> {
+        let body = OllamaRequest {
+            model: &request.model,
+            prompt: &request.prompt,
+            system: request.system.as_deref(),
+            stream,
+            options: &request.options,
+            format: request.format.as_ref(),
+            keep_alive: self.keep_alive.as_deref(),
+        };
+        self.post("/api/generate", &body).await
+    }
+
+    async fn post_chat(&self, request: &ChatRequest) -> Result<reqwest::Response, LlmError> {
+        let body = OllamaChatRequest {
+            model: &request.model,
+            messages: &request.messages,
+            stream: true,
+            options: &request.options,
+            format: request.format.as_ref(),
+            keep_alive: self.keep_alive.as_deref(),
+        };
+        self.post("/api/chat", &body).await
+    }
+
+    async fn post(&self, path: &str, body: &impl Serialize) -> Result<reqwest::Response, LlmError> {
+        let response = self
+            .client
+            .post(self.url(path))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| send_error(e, "Ollama"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LlmError::Http { status: status.as_u16(), body });
+        }
+
+        Ok(response)
+    }
+}
+
+impl Default for OllamaBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_OLLAMA_URL)
+    }
+}
+
+/// Map a reqwest send error to an LlmError
+pub(crate) fn send_error(e: reqwest::Error, server: &str) -> LlmError {
+    if e.is_timeout() {
+        LlmError::Timeout
+    } else {
+        LlmError::Connection(format!("Failed to call {}: {}. Is {} running?", server, e, server))
+    }
+}
+
+/// Read Ollama's NDJSON stream, one JSON object per line, until `on_line`
+/// returns false. Dropping the response closes the connection, which makes
+/// Ollama stop generating.
+async fn read_lines<T: DeserializeOwned>(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(T) -> Result<bool, LlmError>,
+) -> Result<(), LlmError> {
+    let mut pending: Vec<u8> = Vec::new();
+
+    while let Some(bytes) = response
+        .chunk()
+        .await
+        .map_err(|e| send_error(e, "Ollama"))?
+    {
+        pending.extend_from_slice(&bytes);
+
+        while let Some(newline) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let parsed: T = serde_json::from_str(line)
+                .map_err(|e| LlmError::InvalidResponse(format!("Bad stream chunk: {}", e)))?;
+            if !on_line(parsed)? {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect a streamed generation, passing each token to `on_token`.
+/// Only the final chunk has token counts, so a stream stopped early by
+/// `on_token` reports usage estimated from `prompt_chars` and the chunks read.
+async fn read_stream(
+    response: reqwest::Response,
+    model: &str,
+    prompt_chars: usize,
+    on_token: TokenSink<'_>,
+) -> Result<Generation, LlmError> {
+    let mut text = String::new();
+    let mut usage = None;
+    let mut chunks = 0;
+
+    read_lines(response, |chunk: OllamaStreamChunk| {
+        if let Some(error) = chunk.error {
+            return Err(LlmError::InvalidResponse(error));
+        }
+
+        let token = chunk.text();
+        if !token.is_empty() {
+            text.push_str(token);
+            chunks += 1;
+            if !on_token(token) {
+                // Dropping the response makes Ollama stop generating
+                println!("[LLM] Stopping generation early ({} chars)", text.len());
+                usage = Some(usage::estimate(prompt_chars, chunks));
+                return Ok(false);
+            }
+        }
+
+        if chunk.done {
+            usage = chunk.metrics.usage();
+            return Ok(false);
+        }
+        Ok(true)
+    })
+    .await?;
+
+    Ok(Generation {
+        text,
+        model: model.to_string(),
+        usage,
+    })
+}
+
+#[async_trait]
+impl LlmBackend for OllamaBackend {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn breaker(&self) -> &Breaker {
+        &self.breaker
+    }
+
+    async fn generate(&self, request: &GenerateRequest) -> Result<Generation, LlmError> {
+        let response = self.post_generate(request, false).await?;
+
+        let ollama_response: OllamaResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse Ollama response: {}", e)))?;
+
+        Ok(Generation {
+            text: ollama_response.response,
+            model: request.model.clone(),
+            usage: ollama_response.metrics.usage(),
+        })
+    }
+
+    async fn generate_stream(
+        &self,
+        request: &GenerateRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let response = self.post_generate(request, true).await?;
+        let prompt_chars = request.prompt.len() + request.system.as_ref().map_or(0, |s| s.len());
+        read_stream(response, &request.model, prompt_chars, on_token).await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let response = self.post_chat(request).await?;
+        let prompt_chars = request.messages.iter().map(|m| m.content.len()).sum();
+        read_stream(response, &request.model, prompt_chars, on_token).await
+    }
+
+    async fn health(&self) -> bool {
+        self.client
+            .get(self.url("/api/tags"))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let response = self
+            .client
+            .get(self.url("/api/tags"))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| send_error(e, "Ollama"))?;
+
+        let tags: TagsResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse /api/tags: {}", e)))?;
+
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+}
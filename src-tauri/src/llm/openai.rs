@@ -0,0 +1,472 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+use super::backend::{
+    ChatMessage, ChatRequest, ChatRole, GenerateRequest, Generation, GenerationOptions, LlmBackend, LlmError,
+    TokenSink,
+};
+use super::ollama::send_error;
+use super::retry::Breaker;
+use crate::types::LlmUsage;
+
+const OPENAI_TIMEOUT_SECS: u64 = 120;
+/// llama.cpp-server's default; vLLM listens on :8000
+pub const DEFAULT_OPENAI_URL: &str = "http://localhost:8080";
+
+#[derive(Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<&'a [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    /// GBNF grammar (llama.cpp-server extension)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grammar: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct WireMessage<'a> {
+    role: &'static str,
+    content: Cow<'a, str>,
+}
+
+impl<'a> From<&'a ChatMessage> for WireMessage<'a> {
+    fn from(message: &'a ChatMessage) -> Self {
+        match message.role {
+            ChatRole::System => WireMessage { role: "system", content: Cow::Borrowed(&message.content) },
+            ChatRole::User => WireMessage { role: "user", content: Cow::Borrowed(&message.content) },
+            ChatRole::Assistant => WireMessage { role: "assistant", content: Cow::Borrowed(&message.content) },
+            // "tool" needs a tool_call_id we don't have, so report results as the user
+            ChatRole::Tool => WireMessage {
+                role: "user",
+                content: Cow::Owned(format!("Result: {}", message.content)),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatCompletionResponse {
+    model: Option<String>,
+    choices: Vec<ChatChoice>,
+    usage: Option<CompletionUsage>,
+}
+
+#[derive(Deserialize)]
+struct CompletionUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatChoiceMessage {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// `/v1/chat/completions` as served by llama.cpp-server, vLLM and friends
+pub struct OpenAiBackend {
+    base_url: String,
+    api_key: Option<String>,
+    /// The server accepts llama.cpp's `grammar` field
+    grammar: bool,
+    client: reqwest::Client,
+    breaker: Breaker,
+}
+
+impl OpenAiBackend {
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(OPENAI_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            base_url: base_url.trim_end_matches('/').trim_end_matches("/v1").to_string(),
+            api_key: None,
+            grammar: false,
+            client,
+            breaker: Breaker::new(),
+        }
+    }
+
+    /// Send `Authorization: Bearer <key>` (vLLM's `--api-key`)
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key.filter(|k| !k.is_empty());
+        self
+    }
+
+    /// Send request grammars to the server (llama.cpp-server only)
+    pub fn with_grammar(mut self, grammar: bool) -> Self {
+        self.grammar = grammar;
+        self
+    }
+
+    /// Replace the default request timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1{}", self.base_url, path)
+    }
+
+    fn authorize(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    async fn complete(
+        &self,
+        model: &str,
+        messages: Vec<WireMessage<'_>>,
+        options: &GenerationOptions,
+        format: Option<&serde_json::Value>,
+        grammar: Option<&str>,
+    ) -> Result<Generation, LlmError> {
+        let body = ChatCompletionRequest {
+            model,
+            messages,
+            stream: false,
+            max_tokens: options.num_predict,
+            temperature: options.temperature,
+            seed: options.seed,
+            stop: options.stop.as_deref(),
+            response_format: format.map(|schema| {
+                serde_json::json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": "response", "schema": schema },
+                })
+            }),
+            grammar: grammar.filter(|_| self.grammar),
+        };
+
+        let response = self
+            .authorize(self.client.post(self.url("/chat/completions")))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| send_error(e, "the LLM server"))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LlmError::Http { status: status.as_u16(), body });
+        }
+
+        let completion: ChatCompletionResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse chat completion: {}", e)))?;
+
+        let text = completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|c| c.message.content)
+            .ok_or_else(|| LlmError::InvalidResponse("Chat completion has no choices".to_string()))?;
+
+        // OpenAI-compatible servers report tokens but not timings
+        let usage = completion.usage.map(|u| LlmUsage {
+            prompt_tokens: u.prompt_tokens,
+            completion_tokens: u.completion_tokens,
+            ..Default::default()
+        });
+
+        Ok(Generation {
+            text,
+            model: completion.model.unwrap_or_else(|| model.to_string()),
+            usage,
+        })
+    }
+}
+
+impl Default for OpenAiBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_OPENAI_URL)
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiBackend {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn breaker(&self) -> &Breaker {
+        &self.breaker
+    }
+
+    async fn generate(&self, request: &GenerateRequest) -> Result<Generation, LlmError> {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(WireMessage { role: "system", content: Cow::Borrowed(system) });
+        }
+        messages.push(WireMessage { role: "user", content: Cow::Borrowed(&request.prompt) });
+
+        self.complete(
+            &request.model,
+            messages,
+            &request.options,
+            request.format.as_ref(),
+            request.grammar.as_deref(),
+        )
+        .await
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: TokenSink<'_>,
+    ) -> Result<Generation, LlmError> {
+        let messages = request.messages.iter().map(WireMessage::from).collect();
+        let generation = self
+            .complete(
+                &request.model,
+                messages,
+                &request.options,
+                request.format.as_ref(),
+                request.grammar.as_deref(),
+            )
+            .await?;
+        on_token(&generation.text);
+        Ok(generation)
+    }
+
+    fn supports_grammar(&self) -> bool {
+        self.grammar
+    }
+
+    async fn health(&self) -> bool {
+        self.authorize(self.client.get(self.url("/models")))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let response = self
+            .authorize(self.client.get(self.url("/models")))
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(|e| send_error(e, "the LLM server"))?;
+
+        let models: ModelsResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse /v1/models: {}", e)))?;
+
+        Ok(models.data.into_iter().map(|m| m.id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::LlmCallType;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one canned HTTP response and hand back the raw request body
+    async fn stub_server(status: &'static str, body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+
+            // Read headers, then as many body bytes as Content-Length says
+            let request = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                let text = String::from_utf8_lossy(&buf).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let content_length = text[..header_end]
+                        .lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if buf.len() >= header_end + 4 + content_length || n == 0 {
+                        break text;
+                    }
+                }
+            };
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request.split("\r\n\r\n").nth(1).unwrap_or("").to_string()
+        });
+
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_generate_sends_chat_completion() {
+        let (url, server) = stub_server(
+            "200 OK",
+            r#"{"model":"qwen","choices":[{"index":0,"message":{"role":"assistant","content":"open Safari"}}],"usage":{"prompt_tokens":31,"completion_tokens":3}}"#,
+        )
+        .await;
+
+        let backend = OpenAiBackend::new(&url);
+        let mut request = GenerateRequest::new("qwen", "Goal: Open Safari", LlmCallType::ActionDecision);
+        request.system = Some("You control a Mac.".to_string());
+        request.options.temperature = Some(0.0);
+        request.options.seed = Some(42);
+        request.options.num_ctx = Some(4096);
+        request.options.num_predict = Some(256);
+        request.options.stop = Some(vec!["\n".to_string()]);
+
+        let generation = backend.generate(&request).await.unwrap();
+        assert_eq!(generation.text, "open Safari");
+        assert_eq!(generation.usage.map(|u| u.completion_tokens), Some(3));
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["role"], "user");
+        assert_eq!(body["messages"][1]["content"], "Goal: Open Safari");
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["seed"], 42);
+        assert!(body.get("num_ctx").is_none());
+        assert_eq!(body["stop"][0], "\n");
+        assert!(body.get("response_format").is_none());
+        assert!(body.get("grammar").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_schema_sent_as_response_format() {
+        let (url, server) = stub_server(
+            "200 OK",
+            r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"{\"tools\":[]}"}}]}"#,
+        )
+        .await;
+
+        let backend = OpenAiBackend::new(&url);
+        let mut request = GenerateRequest::new("qwen", "Open Safari", LlmCallType::ActionDecision);
+        request.format = Some(serde_json::json!({"type": "object"}));
+        backend.generate(&request).await.unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["schema"]["type"], "object");
+    }
+
+    #[tokio::test]
+    async fn test_grammar_sent_to_llama_cpp() {
+        let (url, server) = stub_server(
+            "200 OK",
+            r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"wait 500"}}]}"#,
+        )
+        .await;
+
+        let backend = OpenAiBackend::new(&url).with_grammar(true);
+        let mut request = GenerateRequest::new("qwen", "Wait a bit", LlmCallType::ActionDecision);
+        request.grammar = Some(r#"root ::= "wait " [0-9]+"#.to_string());
+        backend.generate(&request).await.unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        assert_eq!(body["grammar"], r#"root ::= "wait " [0-9]+"#);
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_history() {
+        let (url, server) = stub_server(
+            "200 OK",
+            r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"press enter"}}]}"#,
+        )
+        .await;
+
+        let backend = OpenAiBackend::new(&url);
+        let request = ChatRequest::new(
+            "qwen",
+            vec![
+                ChatMessage::system("You control a Mac."),
+                ChatMessage::user("Goal: Search for cats"),
+                ChatMessage::assistant("type cats"),
+                ChatMessage::tool("OK"),
+            ],
+            LlmCallType::ActionDecision,
+        );
+
+        let mut tokens = Vec::new();
+        let generation = backend
+            .chat_stream(&request, &mut |t: &str| {
+                tokens.push(t.to_string());
+                true
+            })
+            .await
+            .unwrap();
+        assert_eq!(generation.text, "press enter");
+        assert_eq!(tokens, vec!["press enter"]);
+
+        let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+        let roles: Vec<&str> = body["messages"].as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+        assert_eq!(body["messages"][3]["content"], "Result: OK");
+    }
+
+    #[tokio::test]
+    async fn test_generate_reports_http_errors() {
+        let (url, _server) = stub_server("503 Service Unavailable", r#"{"error":"loading model"}"#).await;
+
+        let backend = OpenAiBackend::new(&url);
+        let request = GenerateRequest::new("qwen", "hi", LlmCallType::Verification);
+
+        match backend.generate(&request).await {
+            Err(LlmError::Http { status, .. }) => assert_eq!(status, 503),
+            other => panic!("expected HTTP error, got {:?}", other.map(|g| g.text)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_models() {
+        let (url, _server) = stub_server("200 OK", r#"{"object":"list","data":[{"id":"qwen2.5-0.5b"}]}"#).await;
+
+        let backend = OpenAiBackend::new(&format!("{}/v1", url));
+        assert_eq!(backend.list_models().await.unwrap(), vec!["qwen2.5-0.5b"]);
+    }
+}
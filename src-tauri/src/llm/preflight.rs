@@ -0,0 +1,302 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter};
+
+use super::backend::LlmBackend;
+use super::ollama::{OllamaBackend, PullProgress, DEFAULT_OLLAMA_URL};
+use super::openai::OpenAiBackend;
+use crate::settings::{self, Settings};
+use crate::types::{ModelState, ModelStatusEvent};
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// A run was requested that the current one has not covered yet
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// A server to check and the models expected on it
+struct Server {
+    url: String,
+    /// (model, warm it up)
+    models: Vec<(String, bool)>,
+    /// Serves the text backend (otherwise only vision)
+    text: bool,
+    /// Ollama, so models can be pulled and warmed
+    ollama: bool,
+}
+
+/// Check every configured model, pull missing ones and load the main ones,
+/// reporting progress as `model_status` events
+pub async fn run(app: &AppHandle) {
+    if !settings::current().preflight.enabled {
+        return;
+    }
+    // Set before claiming RUNNING so a run in progress picks the request up
+    PENDING.store(true, Ordering::SeqCst);
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        println!("[PREFLIGHT] Already running, will run again with the new settings");
+        return;
+    }
+
+    loop {
+        while PENDING.swap(false, Ordering::SeqCst) {
+            let settings = settings::current();
+            if !settings.preflight.enabled {
+                continue;
+            }
+            for server in servers(&settings) {
+                check_server(app, &settings, &server).await;
+            }
+        }
+
+        RUNNING.store(false, Ordering::SeqCst);
+        // A request that came in after the last check but before RUNNING was
+        // cleared found it set; run for it unless another run has started
+        if !PENDING.load(Ordering::SeqCst) || RUNNING.swap(true, Ordering::SeqCst) {
+            break;
+        }
+    }
+}
+
+fn servers(settings: &Settings) -> Vec<Server> {
+    let ollama = settings.llm.backend == "ollama";
+    let text_url = match settings.llm.base_url.as_str() {
+        "" if ollama => DEFAULT_OLLAMA_URL.to_string(),
+        "" => super::openai::DEFAULT_OPENAI_URL.to_string(),
+        url => url.to_string(),
+    };
+    let vision_url = settings.vision.base_url.clone();
+    let vision = Server {
+        url: vision_url.clone(),
+        models: settings.vision_models(),
+        text: false,
+        ollama: true,
+    };
+
+    // The embedded model is loaded with the settings; only vision needs a server
+    if settings.llm.backend == "embedded" {
+        return vec![vision];
+    }
+
+    let mut text = Server {
+        url: text_url,
+        models: settings.text_models(),
+        text: true,
+        ollama,
+    };
+
+    // One server for both: check it once
+    if ollama && same_url(&text.url, &vision_url) {
+        for model in settings.vision_models() {
+            if !text.models.iter().any(|(m, _)| *m == model.0) {
+                text.models.push(model);
+            }
+        }
+        return vec![text];
+    }
+
+    vec![text, vision]
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
+async fn check_server(app: &AppHandle, settings: &Settings, server: &Server) {
+    let ollama = OllamaBackend::new(&server.url).with_keep_alive(Some(settings.preflight.keep_alive.clone()));
+    let text_models = settings.text_models();
+    let is_text_model = |model: &str| server.text && text_models.iter().any(|(m, _)| m == model);
+
+    // An earlier run already fell back to the embedded model; Ollama's text models don't matter
+    let mut models = server.models.clone();
+    if server.ollama && super::backend().name() == "embedded" {
+        models.retain(|(model, _)| {
+            if is_text_model(model) {
+                emit(app, server, model, ModelState::Ready, Some(EMBEDDED_DETAIL.to_string()));
+            }
+            !is_text_model(model)
+        });
+        if models.is_empty() {
+            return;
+        }
+    }
+
+    for (model, _) in &models {
+        emit(app, server, model, ModelState::Checking, None);
+    }
+
+    // Ask the server itself, not whichever backend is active
+    let probe: Box<dyn LlmBackend> = if server.ollama {
+        Box::new(OllamaBackend::new(&server.url))
+    } else {
+        Box::new(OpenAiBackend::new(&server.url).with_api_key(settings.llm.api_key.clone()))
+    };
+    if !probe.health().await {
+        println!("[PREFLIGHT] {} is not reachable", server.url);
+        let embedded = server.text && server.ollama && settings.embedded.fallback && use_embedded(settings).await;
+        for (model, _) in &models {
+            if embedded && is_text_model(model) {
+                emit(app, server, model, ModelState::Ready, Some(EMBEDDED_DETAIL.to_string()));
+            } else {
+                let detail = format!("{} is not reachable", server.url);
+                emit(app, server, model, ModelState::Failed, Some(detail));
+            }
+        }
+        return;
+    }
+
+    let available = match probe.list_models().await {
+        Ok(models) => models,
+        Err(e) => {
+            for (model, _) in &models {
+                emit(app, server, model, ModelState::Failed, Some(e.to_string()));
+            }
+            return;
+        }
+    };
+
+    for (model, main) in &models {
+        if !has_model(&available, model) {
+            if !server.ollama {
+                let detail = format!("{} does not serve this model", server.url);
+                emit(app, server, model, ModelState::Failed, Some(detail));
+                continue;
+            }
+            if !settings.preflight.auto_pull {
+                let detail = format!("Not installed. Run `ollama pull {}`", model);
+                emit(app, server, model, ModelState::Failed, Some(detail));
+                continue;
+            }
+            if let Err(e) = pull(app, server, &ollama, model).await {
+                println!("[PREFLIGHT] Failed to pull {}: {}", model, e);
+                emit(app, server, model, ModelState::Failed, Some(e.to_string()));
+                continue;
+            }
+        }
+
+        // Fallback models are only pulled; loading them all could exhaust memory
+        if *main && settings.preflight.warm_up && server.ollama {
+            emit(app, server, model, ModelState::Warming, None);
+            match ollama.warm_up(model).await {
+                Ok(usage) => {
+                    let load_ms = usage.map(|u| u.load_duration_ms).unwrap_or(0);
+                    println!("[PREFLIGHT] {} loaded in {}ms", model, load_ms);
+                    emit(app, server, model, ModelState::Ready, Some(format!("Loaded in {}ms", load_ms)));
+                }
+                Err(e) => {
+                    println!("[PREFLIGHT] Failed to load {}: {}", model, e);
+                    emit(app, server, model, ModelState::Failed, Some(e.to_string()));
+                }
+            }
+        } else {
+            emit(app, server, model, ModelState::Ready, None);
+        }
+    }
+}
+
+const EMBEDDED_DETAIL: &str = "Ollama is not running; using the embedded model";
+
+/// Switch text calls to the embedded GGUF model. False if it can't be loaded.
+async fn use_embedded(settings: &Settings) -> bool {
+    let mut config = settings.backend_config();
+    config.kind = "embedded".to_string();
+
+    let loaded = tokio::task::spawn_blocking(move || super::create_backend(&config))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+    match loaded {
+        Ok(backend) => {
+            println!("[PREFLIGHT] Ollama is not running, falling back to the embedded model");
+            super::set_backend(backend);
+            true
+        }
+        Err(e) => {
+            println!("[PREFLIGHT] No embedded fallback: {}", e);
+            false
+        }
+    }
+}
+
+/// Pull a model, sending an event whenever the status or whole percentage changes
+async fn pull(app: &AppHandle, server: &Server, ollama: &OllamaBackend, model: &str) -> Result<(), super::LlmError> {
+    println!("[PREFLIGHT] Pulling {} from {}", model, server.url);
+    let mut last: Option<(String, u64)> = None;
+
+    ollama
+        .pull(model, &mut |progress: &PullProgress| {
+            let percent = match (progress.completed, progress.total) {
+                (Some(completed), Some(total)) if total > 0 => completed * 100 / total,
+                _ => 0,
+            };
+            let key = (progress.status.clone(), percent);
+            if last.as_ref() == Some(&key) {
+                return;
+            }
+            last = Some(key);
+
+            let _ = app.emit(
+                "model_status",
+                &ModelStatusEvent {
+                    model: model.to_string(),
+                    server: server.url.clone(),
+                    state: ModelState::Pulling,
+                    detail: Some(progress.status.clone()),
+                    completed: progress.completed,
+                    total: progress.total,
+                },
+            );
+        })
+        .await
+}
+
+fn emit(app: &AppHandle, server: &Server, model: &str, state: ModelState, detail: Option<String>) {
+    let _ = app.emit(
+        "model_status",
+        &ModelStatusEvent {
+            model: model.to_string(),
+            server: server.url.clone(),
+            state,
+            detail,
+            completed: None,
+            total: None,
+        },
+    );
+}
+
+/// Whether `model` is in Ollama's list; an untagged name means `:latest`
+fn has_model(available: &[String], model: &str) -> bool {
+    available.iter().any(|name| {
+        name == model || (!model.contains(':') && name.strip_suffix(":latest") == Some(model))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_model() {
+        let available = vec!["llava:latest".to_string(), "qwen2.5:7b".to_string()];
+        assert!(has_model(&available, "llava"));
+        assert!(has_model(&available, "llava:latest"));
+        assert!(has_model(&available, "qwen2.5:7b"));
+        assert!(!has_model(&available, "qwen2.5"));
+        assert!(!has_model(&available, "qwen2.5:0.5b"));
+    }
+
+    #[test]
+    fn test_shared_server_is_checked_once() {
+        let shared = servers(&Settings::default());
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].models.len(), 2);
+
+        let mut settings = Settings::default();
+        settings.llm.backend = "openai".into();
+        let separate = servers(&settings);
+        assert_eq!(separate.len(), 2);
+        assert!(!separate[0].ollama && separate[1].ollama);
+
+        settings.llm.backend = "embedded".into();
+        let vision_only = servers(&settings);
+        assert_eq!(vision_only.len(), 1);
+        assert!(!vision_only[0].text);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use super::backend::ChatMessage;
+use crate::settings;
+use crate::types::RepairStats;
+
+/// Repair outcomes per prompt since launch
+static STATS: Mutex<Option<HashMap<&'static str, RepairStats>>> = Mutex::new(None);
+
+/// Call the model and parse its reply. When parsing fails, the bad reply and
+/// the error go back to the model with a request to fix it, up to
+/// `limits.max_repairs` times. `prompt` names the prompt in the stats.
+///
+/// Returns the parsed value and the reply it came from. Repair turns are not
+/// added to `messages`.
+pub async fn call_with_repair<T, F, Fut>(
+    prompt: &'static str,
+    messages: &[ChatMessage],
+    mut call: F,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<(T, String), String>
+where
+    F: FnMut(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let max_repairs = settings::current().limits.max_repairs;
+    let mut conversation = messages.to_vec();
+    let mut response = call(conversation.clone()).await?;
+    let mut repairs = 0;
+
+    loop {
+        let error = match parse(&response) {
+            Ok(value) => {
+                record(prompt, repairs, true);
+                if repairs > 0 {
+                    println!("[REPAIR] {} fixed after {} repair(s)", prompt, repairs);
+                }
+                return Ok((value, response));
+            }
+            Err(e) => e,
+        };
+
+        if repairs >= max_repairs {
+            record(prompt, repairs, false);
+            return Err(error);
+        }
+
+        repairs += 1;
+        println!("[REPAIR] {} reply did not parse ({}), asking for a fix ({}/{})", prompt, error, repairs, max_repairs);
+        conversation.push(ChatMessage::assistant(response.trim()));
+        conversation.push(ChatMessage::user(repair_instruction(&error)));
+        response = call(conversation.clone()).await?;
+    }
+}
+
+fn repair_instruction(error: &str) -> String {
+    format!(
+        "That answer could not be used: {}\nReply again with ONLY the corrected answer, in exactly the format asked for.",
+        error
+    )
+}
+
+fn record(prompt: &'static str, repairs: u32, parsed: bool) {
+    if let Ok(mut stats) = STATS.lock() {
+        stats.get_or_insert_with(HashMap::new).entry(prompt).or_default().record(repairs, parsed);
+    }
+}
+
+impl RepairStats {
+    /// Add one call that needed `repairs` repair prompts
+    fn record(&mut self, repairs: u32, parsed: bool) {
+        self.calls += 1;
+        self.repair_attempts += repairs;
+        if repairs > 0 || !parsed {
+            self.parse_failures += 1;
+        }
+        if repairs > 0 && parsed {
+            self.repaired += 1;
+        }
+    }
+}
+
+/// Repair outcomes per prompt since launch
+pub fn snapshot() -> HashMap<String, RepairStats> {
+    STATS
+        .lock()
+        .ok()
+        .and_then(|stats| stats.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(prompt, stats)| (prompt.to_string(), stats))
+        .collect()
+}
+
+/// Print how often each prompt needed repairs
+pub fn log_summary() {
+    let mut stats: Vec<_> = snapshot().into_iter().filter(|(_, s)| s.parse_failures > 0).collect();
+    stats.sort_by(|a, b| a.0.cmp(&b.0));
+    for (prompt, s) in stats {
+        println!(
+            "[REPAIR] {}: {}/{} parse failures repaired ({:.0}%), {} calls",
+            prompt,
+            s.repaired,
+            s.parse_failures,
+            s.success_rate().unwrap_or(0.0) * 100.0,
+            s.calls
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(text: &str) -> Result<u32, String> {
+        text.trim().parse().map_err(|_| format!("'{}' is not a number", text.trim()))
+    }
+
+    #[tokio::test]
+    async fn test_bad_reply_is_sent_back() {
+        let mut replies = vec!["5".to_string(), "five".to_string()];
+        let mut seen = vec![];
+        let call = |messages: Vec<ChatMessage>| {
+            seen.push(messages);
+            let reply = replies.pop().unwrap();
+            async move { Ok(reply) }
+        };
+        let (value, reply) = call_with_repair("test_repair", &[ChatMessage::user("Pick a number")], call, parse_number)
+            .await
+            .unwrap();
+
+        assert_eq!((value, reply.as_str()), (5, "5"));
+        assert_eq!(seen.len(), 2);
+        let repair = &seen[1];
+        assert_eq!(repair.len(), 3);
+        assert_eq!(repair[1].content, "five");
+        assert!(repair[2].content.contains("'five' is not a number"));
+
+        let stats = &snapshot()["test_repair"];
+        assert_eq!((stats.calls, stats.parse_failures, stats.repaired), (1, 1, 1));
+        assert_eq!(stats.success_rate(), Some(1.0));
+    }
+
+    #[test]
+    fn test_unrepaired_failures_count() {
+        let mut stats = RepairStats::default();
+        stats.record(0, true);
+        stats.record(2, false);
+        stats.record(1, true);
+        assert_eq!(stats.calls, 3);
+        assert_eq!(stats.parse_failures, 2);
+        assert_eq!(stats.repaired, 1);
+        assert_eq!(stats.repair_attempts, 3);
+        assert_eq!(stats.success_rate(), Some(0.5));
+    }
+}
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::backend::{Generation, LlmError};
+use crate::types::LlmAttempt;
+
+/// How LLM calls are retried when the backend misbehaves
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Retries per model on transient errors (timeouts, 429, 5xx)
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Consecutive connection failures before calls fail fast
+    pub breaker_threshold: u32,
+    /// How long calls fail fast once the breaker opens
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8_000,
+            breaker_threshold: 3,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based), doubling each time
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let ms = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << retry.min(16))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(ms)
+    }
+}
+
+impl LlmError {
+    /// Worth retrying against the same model
+    pub fn is_transient(&self) -> bool {
+        match self {
+            LlmError::Timeout | LlmError::Connection(_) => true,
+            LlmError::Http { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// The server itself is unreachable, so another model won't help
+    fn is_server_down(&self) -> bool {
+        matches!(self, LlmError::Connection(_) | LlmError::CircuitOpen { .. })
+    }
+}
+
+/// Walks through retries of the primary model, then each fallback model.
+/// Call `should_retry` after every attempt until it returns false.
+pub struct Attempts<'a> {
+    models: Vec<String>,
+    policy: RetryPolicy,
+    model_index: usize,
+    retry: u32,
+    started: Instant,
+    log: Vec<LlmAttempt>,
+    /// The breaker of the backend being called
+    breaker: &'a Breaker,
+}
+
+impl<'a> Attempts<'a> {
+    pub fn new(model: &str, fallback_models: &[String], policy: RetryPolicy, breaker: &'a Breaker) -> Self {
+        let mut models = vec![model.to_string()];
+        models.extend(fallback_models.iter().filter(|m| !m.is_empty() && *m != model).cloned());
+        Self {
+            models,
+            policy,
+            model_index: 0,
+            retry: 0,
+            started: Instant::now(),
+            log: vec![],
+            breaker,
+        }
+    }
+
+    /// Model for the next attempt
+    pub fn model(&self) -> &str {
+        &self.models[self.model_index]
+    }
+
+    /// Fail fast while the circuit breaker is open
+    pub fn check_breaker(&self) -> Result<(), LlmError> {
+        self.breaker.check()
+    }
+
+    /// Record the outcome of the last attempt. Returns true if another
+    /// attempt should be made, after sleeping for any backoff.
+    pub async fn should_retry(&mut self, result: &Result<Generation, LlmError>) -> bool {
+        let duration_ms = self.started.elapsed().as_millis() as u64;
+        let mut attempt = LlmAttempt {
+            model: self.model().to_string(),
+            fallback: self.model_index > 0,
+            error: None,
+            duration_ms,
+            backoff_ms: 0,
+        };
+
+        let error = match result {
+            Ok(_) => {
+                self.breaker.record(true, &self.policy);
+                self.log.push(attempt);
+                return false;
+            }
+            Err(e) => e,
+        };
+
+        // Says nothing about the server; give up at once
+        if matches!(error, LlmError::Cancelled) {
+            attempt.error = Some(error.to_string());
+            self.log.push(attempt);
+            return false;
+        }
+
+        if !matches!(error, LlmError::CircuitOpen { .. }) {
+            let reachable = !matches!(error, LlmError::Connection(_) | LlmError::Timeout);
+            self.breaker.record(reachable, &self.policy);
+        }
+        attempt.error = Some(error.to_string());
+
+        let again = if error.is_transient() && self.retry < self.policy.max_retries && self.breaker.check().is_ok() {
+            let backoff = self.policy.backoff(self.retry);
+            attempt.backoff_ms = backoff.as_millis() as u64;
+            println!(
+                "[LLM] {} failed ({}), retrying in {}ms",
+                attempt.model, error, attempt.backoff_ms
+            );
+            self.log.push(attempt);
+            if crate::cancel::sleep(backoff).await.is_err() {
+                return false;
+            }
+            self.retry += 1;
+            true
+        } else if !error.is_server_down() && self.model_index + 1 < self.models.len() {
+            self.model_index += 1;
+            self.retry = 0;
+            println!("[LLM] {} failed ({}), falling back to {}", attempt.model, error, self.model());
+            self.log.push(attempt);
+            true
+        } else {
+            self.log.push(attempt);
+            false
+        };
+
+        self.started = Instant::now();
+        again
+    }
+
+    /// Every attempt made so far
+    pub fn into_log(self) -> Vec<LlmAttempt> {
+        self.log
+    }
+}
+
+/// Trips after repeated connection failures so a dead server doesn't cost
+/// every call its full timeout and retries. Each backend owns one.
+#[derive(Default)]
+pub struct Breaker {
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Breaker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn check(&self) -> Result<(), LlmError> {
+        let Ok(state) = self.state.lock() else {
+            return Ok(());
+        };
+        match state.open_until {
+            Some(until) if Instant::now() < until => Err(LlmError::CircuitOpen {
+                retry_after_ms: (until - Instant::now()).as_millis() as u64,
+            }),
+            // Cooldown over: let calls through again (half-open)
+            _ => Ok(()),
+        }
+    }
+
+    /// `reachable` is false when the server didn't answer at all
+    fn record(&self, reachable: bool, policy: &RetryPolicy) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if reachable {
+            state.consecutive_failures = 0;
+            state.open_until = None;
+            return;
+        }
+
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= policy.breaker_threshold {
+            println!(
+                "[LLM] {} consecutive connection failures, pausing LLM calls for {}s",
+                state.consecutive_failures, policy.breaker_cooldown_secs
+            );
+            state.open_until = Some(Instant::now() + Duration::from_secs(policy.breaker_cooldown_secs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 1,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 2,
+            breaker_threshold: 100,
+            breaker_cooldown_secs: 1,
+        }
+    }
+
+    fn ok() -> Result<Generation, LlmError> {
+        Ok(Generation { text: "ok".into(), model: "m".into(), usage: None })
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(1_000));
+        assert_eq!(policy.backoff(10), Duration::from_millis(8_000));
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_falls_back() {
+        let breaker = Breaker::new();
+        let mut attempts = Attempts::new("big", &["small".to_string()], quick_policy(), &breaker);
+
+        // Transient error: retry the same model once
+        assert!(attempts.should_retry(&Err(LlmError::Http { status: 503, body: String::new() })).await);
+        assert_eq!(attempts.model(), "big");
+
+        // Retries used up: move on to the fallback
+        assert!(attempts.should_retry(&Err(LlmError::Timeout)).await);
+        assert_eq!(attempts.model(), "small");
+
+        assert!(!attempts.should_retry(&ok()).await);
+
+        let log = attempts.into_log();
+        assert_eq!(log.len(), 3);
+        assert!(log[0].backoff_ms > 0);
+        assert!(!log[1].fallback);
+        assert!(log[2].fallback && log[2].error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_model_not_found_skips_retries() {
+        let breaker = Breaker::new();
+        let mut attempts = Attempts::new("missing", &["small".to_string()], quick_policy(), &breaker);
+        assert!(attempts.should_retry(&Err(LlmError::Http { status: 404, body: "model not found".into() })).await);
+        assert_eq!(attempts.model(), "small");
+        assert!(!attempts.should_retry(&Err(LlmError::InvalidResponse("bad".into()))).await);
+    }
+
+    #[tokio::test]
+    async fn test_breaker_opens_after_connection_failures() {
+        let policy = RetryPolicy { breaker_threshold: 2, max_retries: 5, ..quick_policy() };
+        let breaker = Breaker::new();
+        let mut attempts = Attempts::new("m", &[], policy.clone(), &breaker);
+        assert!(attempts.should_retry(&Err(LlmError::Connection("refused".into()))).await);
+        // Second failure opens the breaker, so no more retries
+        assert!(!attempts.should_retry(&Err(LlmError::Connection("refused".into()))).await);
+        assert!(matches!(attempts.check_breaker(), Err(LlmError::CircuitOpen { .. })));
+
+        // Another backend's breaker is unaffected
+        let other = Breaker::new();
+        assert!(Attempts::new("m", &[], policy, &other).check_breaker().is_ok());
+    }
+}
@@ -0,0 +1,80 @@
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use super::backend::LlmError;
+
+/// JSON Schema for `T`, with every subschema inlined.
+/// Sent as Ollama's `format` / OpenAI's `response_format` so the
+/// server constrains decoding to valid instances.
+pub fn schema_for<T: JsonSchema>() -> serde_json::Value {
+    let generator = SchemaSettings::draft07()
+        .with(|s| {
+            s.inline_subschemas = true;
+            s.meta_schema = None;
+        })
+        .into_generator();
+    let schema = generator.into_root_schema_for::<T>();
+    serde_json::to_value(schema).unwrap_or_default()
+}
+
+/// Parse a schema-constrained response into `T`.
+/// Servers that ignore the schema may still wrap the JSON in prose or code
+/// fences or get the syntax slightly wrong, so the output is repaired first
+/// if needed (see `json_repair`).
+pub fn parse_structured<T: DeserializeOwned>(text: &str) -> Result<T, LlmError> {
+    match super::json_repair::parse_lenient::<T>(text) {
+        Ok((value, repairs)) => {
+            if !repairs.is_empty() {
+                println!("[LLM] Repaired {} JSON: {:?}", short_type_name::<T>(), repairs);
+            }
+            Ok(value)
+        }
+        Err(e) => Err(LlmError::SchemaViolation {
+            target: short_type_name::<T>(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::tools::{Tool, ToolList};
+
+    #[test]
+    fn test_tool_schema_is_inlined() {
+        let schema = schema_for::<ToolList>();
+        let text = schema.to_string();
+        assert!(!text.contains("$ref"));
+        assert!(text.contains("open_app"));
+        assert!(text.contains("step_done"));
+    }
+
+    #[test]
+    fn test_parse_structured() {
+        let list: ToolList =
+            parse_structured(r#"{"tools": [{"tool": "open_app", "params": {"name": "Safari"}}, {"tool": "step_done"}]}"#)
+                .unwrap();
+        assert!(matches!(&list.tools[0], Tool::OpenApp { name } if name == "Safari"));
+        assert!(matches!(list.tools[1], Tool::StepDone));
+
+        // Prose around the object is tolerated
+        let tool: Tool = parse_structured(r#"Sure! {"tool": "wait", "params": {"ms": 300}}"#).unwrap();
+        assert!(matches!(tool, Tool::Wait { ms: 300 }));
+
+        // So are small-model syntax slips
+        let list: ToolList = parse_structured("```json\n{tools: [{'tool': 'step_done'},]}\n```").unwrap();
+        assert!(matches!(list.tools[..], [Tool::StepDone]));
+
+        match parse_structured::<Tool>(r#"{"tool": "teleport"}"#) {
+            Err(LlmError::SchemaViolation { target, .. }) => assert_eq!(target, "Tool"),
+            other => panic!("expected schema violation, got {:?}", other),
+        }
+    }
+}
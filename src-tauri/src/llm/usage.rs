@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use std::sync::Mutex;
+
+use crate::types::{LlmCallType, LlmUsage, SessionUsage};
+
+/// Usage of the running agent session. Only one session runs at a time.
+static SESSION_USAGE: Mutex<Option<SessionUsage>> = Mutex::new(None);
+
+/// Start counting for a new session
+pub fn reset() {
+    if let Ok(mut usage) = SESSION_USAGE.lock() {
+        *usage = Some(SessionUsage::default());
+    }
+}
+
+/// Add a finished call to the running session
+pub fn record(call_type: LlmCallType, duration_ms: u64, usage: Option<&LlmUsage>) {
+    if let Ok(mut session) = SESSION_USAGE.lock() {
+        session
+            .get_or_insert_with(SessionUsage::default)
+            .record(call_type, duration_ms, usage);
+    }
+}
+
+/// Usage of the running session so far
+pub fn snapshot() -> SessionUsage {
+    SESSION_USAGE
+        .lock()
+        .ok()
+        .and_then(|usage| usage.clone())
+        .unwrap_or_default()
+}
+
+/// Print where the running session spent its LLM time
+pub fn log_summary() {
+    let usage = snapshot();
+    println!(
+        "[LLM] {} calls, {} tokens ({} prompt, {} completion), {}ms waiting, {}ms loading models",
+        usage.calls,
+        usage.total_tokens(),
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.duration_ms,
+        usage.load_duration_ms
+    );
+
+    let mut by_type: Vec<_> = usage.by_call_type.iter().collect();
+    by_type.sort_by_key(|(_, u)| std::cmp::Reverse(u.duration_ms));
+    for (call_type, u) in by_type {
+        println!(
+            "[LLM]   {:?}: {} calls, {}ms, {} tokens, {}ms loading",
+            call_type,
+            u.calls,
+            u.duration_ms,
+            u.prompt_tokens + u.completion_tokens,
+            u.load_duration_ms
+        );
+    }
+}
+
+/// Counters Ollama adds to the last chunk of `/api/generate` and `/api/chat`.
+/// Durations are in nanoseconds.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct OllamaMetrics {
+    pub prompt_eval_count: Option<u64>,
+    pub eval_count: Option<u64>,
+    pub total_duration: Option<u64>,
+    pub load_duration: Option<u64>,
+}
+
+impl OllamaMetrics {
+    /// None when the response carried no counters (e.g. an early stop)
+    pub fn usage(&self) -> Option<LlmUsage> {
+        if self.eval_count.is_none() && self.total_duration.is_none() {
+            return None;
+        }
+        Some(LlmUsage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or(0),
+            completion_tokens: self.eval_count.unwrap_or(0),
+            total_duration_ms: self.total_duration.unwrap_or(0) / 1_000_000,
+            load_duration_ms: self.load_duration.unwrap_or(0) / 1_000_000,
+        })
+    }
+}
+
+/// Usage of a stream dropped before Ollama sent its counters: one token per
+/// streamed chunk, and about four prompt characters per token
+pub(crate) fn estimate(prompt_chars: usize, chunks: u64) -> LlmUsage {
+    LlmUsage {
+        prompt_tokens: prompt_chars.div_ceil(4) as u64,
+        completion_tokens: chunks,
+        total_duration_ms: 0,
+        load_duration_ms: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ollama_metrics() {
+        let metrics: OllamaMetrics = serde_json::from_str(
+            r#"{"done":true,"prompt_eval_count":26,"eval_count":12,"total_duration":5043500667,"load_duration":3011000000}"#,
+        )
+        .unwrap();
+        let usage = metrics.usage().unwrap();
+        assert_eq!(usage.prompt_tokens, 26);
+        assert_eq!(usage.completion_tokens, 12);
+        assert_eq!(usage.total_duration_ms, 5043);
+        assert_eq!(usage.load_duration_ms, 3011);
+
+        let empty: OllamaMetrics = serde_json::from_str(r#"{"response":"hi"}"#).unwrap();
+        assert!(empty.usage().is_none());
+
+        let estimated = estimate(102, 7);
+        assert_eq!(estimated.prompt_tokens, 26);
+        assert_eq!(estimated.completion_tokens, 7);
+    }
+
+    #[test]
+    fn test_session_usage_per_call_type() {
+        let mut session = SessionUsage::default();
+        let usage = LlmUsage {
+            prompt_tokens: 100,
+            completion_tokens: 20,
+            total_duration_ms: 900,
+            load_duration_ms: 400,
+        };
+        session.record(LlmCallType::ActionDecision, 1000, Some(&usage));
+        session.record(LlmCallType::ActionDecision, 500, Some(&usage));
+        session.record(LlmCallType::FindElement, 2000, None);
+
+        assert_eq!(session.calls, 3);
+        assert_eq!(session.total_tokens(), 240);
+        assert_eq!(session.load_duration_ms, 800);
+        assert_eq!(session.duration_ms, 3500);
+
+        let action = &session.by_call_type[&LlmCallType::ActionDecision];
+        assert_eq!(action.calls, 2);
+        assert_eq!(action.duration_ms, 1500);
+        assert_eq!(session.by_call_type[&LlmCallType::FindElement].prompt_tokens, 0);
+    }
+}
@@ -52,6 +52,9 @@ struct CGRect {
     size: CGSize,
 }
 
+/// Screen rectangle (x1, y1, x2, y2) in points
+pub type ScreenRect = (i32, i32, i32, i32);
+
 /// Information about a window on screen
 #[derive(Debug, Clone)]
 pub struct WindowInfo {
@@ -130,11 +133,29 @@ pub fn find_otto_window_id() -> Option<u32> {
         .map(|w| w.id)
 }
 
-/// Capture screen excluding Otto window using CGWindowListCreateImage
-pub fn capture_screen_excluding_otto() -> Result<Vec<u8>, String> {
+/// Image of `rect` (screen points) with everything below the Otto window,
+/// so the overlay never shows up in what the vision model sees. Null on failure.
+unsafe fn create_image_excluding_otto(rect: CGRect) -> *const c_void {
     // Find Otto window to exclude
     let otto_window_id = find_otto_window_id().unwrap_or(0);
 
+    if otto_window_id > 0 {
+        println!("[SCREENSHOT] Excluding Otto window ID: {}", otto_window_id);
+        // kCGWindowListOptionOnScreenBelowWindow = 1 << 1 = 2
+        CGWindowListCreateImage(rect, 2 | 16, otto_window_id, kCGWindowImageDefault)
+    } else {
+        println!("[SCREENSHOT] No Otto window found, capturing everything");
+        CGWindowListCreateImage(
+            rect,
+            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+            kCGNullWindowID,
+            kCGWindowImageDefault,
+        )
+    }
+}
+
+/// Capture screen excluding Otto window using CGWindowListCreateImage
+pub fn capture_screen_excluding_otto() -> Result<Vec<u8>, String> {
     unsafe {
         let display_bounds = CGDisplay::main().bounds();
         let rect = CGRect {
@@ -148,21 +169,7 @@ pub fn capture_screen_excluding_otto() -> Result<Vec<u8>, String> {
             },
         };
 
-        // Create image excluding the Otto window
-        let image = if otto_window_id > 0 {
-            println!("[SCREENSHOT] Excluding Otto window ID: {}", otto_window_id);
-            // kCGWindowListOptionOnScreenBelowWindow = 1 << 1 = 2
-            CGWindowListCreateImage(rect, 2 | 16, otto_window_id, kCGWindowImageDefault)
-        } else {
-            println!("[SCREENSHOT] No Otto window found, capturing full screen");
-            CGWindowListCreateImage(
-                rect,
-                kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
-                kCGNullWindowID,
-                kCGWindowImageDefault,
-            )
-        };
-
+        let image = create_image_excluding_otto(rect);
         if image.is_null() {
             return Err("Failed to capture screen".to_string());
         }
@@ -173,33 +180,39 @@ pub fn capture_screen_excluding_otto() -> Result<Vec<u8>, String> {
     }
 }
 
-/// Capture a specific region of the screen
-pub fn capture_region(x1: i32, y1: i32, x2: i32, y2: i32) -> Result<Vec<u8>, String> {
-    let x = x1.min(x2) as f64;
-    let y = y1.min(y2) as f64;
-    let width = (x1 - x2).abs() as f64;
-    let height = (y1 - y2).abs() as f64;
+/// Capture a specific region of the screen, without the Otto window.
+/// Returns the PNG and the rectangle (x1, y1, x2, y2) it covers once
+/// clipped to the main display.
+pub fn capture_region(x1: i32, y1: i32, x2: i32, y2: i32) -> Result<(Vec<u8>, ScreenRect), String> {
+    let display = CGDisplay::main().bounds();
+    let left = x1.min(x2).max(display.origin.x as i32);
+    let top = y1.min(y2).max(display.origin.y as i32);
+    let right = x1.max(x2).min((display.origin.x + display.size.width) as i32);
+    let bottom = y1.max(y2).min((display.origin.y + display.size.height) as i32);
+    if left >= right || top >= bottom {
+        return Err("Region is off screen".to_string());
+    }
 
     unsafe {
         let rect = CGRect {
-            origin: CGPoint { x, y },
-            size: CGSize { width, height },
+            origin: CGPoint {
+                x: left as f64,
+                y: top as f64,
+            },
+            size: CGSize {
+                width: (right - left) as f64,
+                height: (bottom - top) as f64,
+            },
         };
 
-        let image = CGWindowListCreateImage(
-            rect,
-            kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
-            kCGNullWindowID,
-            kCGWindowImageDefault,
-        );
-
+        let image = create_image_excluding_otto(rect);
         if image.is_null() {
             return Err("Failed to capture region".to_string());
         }
 
         let result = cgimage_to_png(image);
         CGImageRelease(image);
-        result
+        Ok((result?, (left, top, right, bottom)))
     }
 }
 
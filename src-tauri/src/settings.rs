@@ -0,0 +1,685 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+use tauri::{AppHandle, Manager};
+
+use crate::llm::{self, GenerationOptions, RetryPolicy};
+use crate::types::LlmCallType;
+use crate::vision;
+
+/// Bumped whenever the file layout changes; see `migrate`
+pub const SETTINGS_VERSION: u32 = 3;
+
+const SETTINGS_FILE: &str = "settings.json";
+const EMBEDDED_MODEL_FILE: &str = "model.gguf";
+const OCR_MODEL_DIR: &str = "ocr";
+
+/// User-editable configuration, stored as JSON in the app config dir
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub llm: LlmSettings,
+    pub vision: VisionSettings,
+    pub limits: LimitSettings,
+    pub retry: RetryPolicy,
+    pub preflight: PreflightSettings,
+    pub embedded: EmbeddedSettings,
+    pub ocr: OcrSettings,
+}
+
+/// Model and sampling options for one kind of LLM call
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CallRoute {
+    /// Empty uses the section's `model`
+    pub model: String,
+    pub options: GenerationOptions,
+    /// Tried in order when the model keeps failing
+    pub fallback_models: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LlmSettings {
+    /// "ollama", "openai" (llama.cpp-server, vLLM, ...) or "embedded".
+    /// Action decisions are grammar-constrained only on "llama.cpp" and
+    /// "embedded"; Ollama's answers are parsed leniently.
+    pub backend: String,
+    /// Empty uses the backend's default URL
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub timeout_secs: u64,
+    /// Per-call-type model and sampling overrides for text calls
+    pub routes: HashMap<LlmCallType, CallRoute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisionSettings {
+    /// Ollama server hosting the vision model
+    pub base_url: String,
+    pub model: String,
+    pub timeout_secs: u64,
+    /// Replay the recorded screenshot and answers in this directory
+    /// instead of asking the model. Empty uses the model.
+    pub replay_dir: String,
+    /// Pick click targets by numbering the detected boxes on the
+    /// screenshot (Set-of-Mark) instead of asking for coordinates.
+    /// Off by default; it costs a detection pass per lookup.
+    pub set_of_mark: bool,
+    /// Side, in screen points, of the region around a located element that
+    /// is captured at full resolution and asked about again. 0 (the default)
+    /// turns it off; it costs one more vision call per coordinate lookup.
+    pub refine_size: u32,
+    /// Detect on an N x N grid of overlapping full-resolution tiles as well
+    /// as the downscaled screenshot, for small icons. 0 or 1 turns it off.
+    pub tile_grid: u32,
+    /// Tiles sent to the vision model at the same time
+    pub tile_concurrency: u32,
+    /// Per-call-type model and sampling overrides for vision calls
+    pub routes: HashMap<LlmCallType, CallRoute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitSettings {
+    /// Tool calls per task in the tool-based agent
+    pub max_steps: usize,
+    /// Actions per session in the goal-based agent
+    pub max_total_actions: u32,
+    /// Attempts per goal before it fails
+    pub max_goal_attempts: u32,
+    /// Times a malformed LLM answer is sent back to be fixed
+    pub max_repairs: u32,
+}
+
+/// Model checks run at startup and after settings change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreflightSettings {
+    pub enabled: bool,
+    /// Download configured models that Ollama doesn't have yet (off: report them as missing)
+    pub auto_pull: bool,
+    /// Load the main models into memory before the first command
+    pub warm_up: bool,
+    /// How long Ollama keeps a model loaded after a request, e.g. "30m" or "-1" (forever)
+    pub keep_alive: String,
+}
+
+/// In-process llama.cpp inference on a GGUF file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddedSettings {
+    /// Empty looks for models/model.gguf next to settings.json, then in the app resources
+    pub model_path: String,
+    /// Use the embedded model for text calls when Ollama isn't reachable
+    pub fallback: bool,
+}
+
+/// Text boxes from OCR, merged into the vision model's elements
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrSettings {
+    /// Read text whenever the models are found; OCR is skipped without them
+    pub enabled: bool,
+    /// Directory with text-detection.rten and text-recognition.rten.
+    /// Empty looks for models/ocr next to settings.json, then in the app resources
+    pub model_dir: String,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            model_dir: String::new(),
+        }
+    }
+}
+
+impl Default for EmbeddedSettings {
+    fn default() -> Self {
+        Self {
+            model_path: String::new(),
+            fallback: true,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            llm: LlmSettings::default(),
+            vision: VisionSettings::default(),
+            limits: LimitSettings::default(),
+            retry: RetryPolicy::default(),
+            preflight: PreflightSettings::default(),
+            embedded: EmbeddedSettings::default(),
+            ocr: OcrSettings::default(),
+        }
+    }
+}
+
+impl Default for LlmSettings {
+    fn default() -> Self {
+        Self {
+            backend: "ollama".to_string(),
+            base_url: String::new(),
+            api_key: None,
+            model: "qwen2.5:7b".to_string(),
+            timeout_secs: 120,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+impl Default for VisionSettings {
+    fn default() -> Self {
+        Self {
+            base_url: llm::ollama::DEFAULT_OLLAMA_URL.to_string(),
+            model: "llava".to_string(),
+            timeout_secs: 60,
+            replay_dir: String::new(),
+            set_of_mark: false,
+            refine_size: 0,
+            tile_grid: 0,
+            tile_concurrency: 2,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+impl Default for PreflightSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            auto_pull: true,
+            warm_up: true,
+            keep_alive: "30m".to_string(),
+        }
+    }
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            max_steps: 50,
+            max_total_actions: 50,
+            max_goal_attempts: 5,
+            max_repairs: 2,
+        }
+    }
+}
+
+impl Settings {
+    /// Check that every value is usable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != SETTINGS_VERSION {
+            return Err(format!("Unsupported settings version: {}", self.version));
+        }
+
+        if !matches!(self.llm.backend.as_str(), "ollama" | "openai" | "llama.cpp" | "vllm" | "embedded") {
+            return Err(format!("Unknown LLM backend: {}", self.llm.backend));
+        }
+        if !self.llm.base_url.is_empty() {
+            validate_url("llm.base_url", &self.llm.base_url)?;
+        }
+        validate_url("vision.base_url", &self.vision.base_url)?;
+
+        if self.llm.model.trim().is_empty() {
+            return Err("llm.model must not be empty".into());
+        }
+        if self.vision.model.trim().is_empty() {
+            return Err("vision.model must not be empty".into());
+        }
+
+        validate_range("llm.timeout_secs", self.llm.timeout_secs, 1, 600)?;
+        validate_range("vision.timeout_secs", self.vision.timeout_secs, 1, 600)?;
+        if self.vision.refine_size != 0 {
+            validate_range("vision.refine_size", self.vision.refine_size as u64, 64, 2_000)?;
+        }
+        validate_range("vision.tile_grid", self.vision.tile_grid as u64, 0, 6)?;
+        validate_range("vision.tile_concurrency", self.vision.tile_concurrency as u64, 1, 16)?;
+        validate_range("limits.max_steps", self.limits.max_steps as u64, 1, 500)?;
+        validate_range("limits.max_total_actions", self.limits.max_total_actions as u64, 1, 500)?;
+        validate_range("limits.max_goal_attempts", self.limits.max_goal_attempts as u64, 1, 20)?;
+        validate_range("limits.max_repairs", self.limits.max_repairs as u64, 0, 5)?;
+        validate_range("retry.max_retries", self.retry.max_retries as u64, 0, 10)?;
+        validate_range("retry.initial_backoff_ms", self.retry.initial_backoff_ms, 1, 60_000)?;
+        validate_range("retry.max_backoff_ms", self.retry.max_backoff_ms, self.retry.initial_backoff_ms, 300_000)?;
+        validate_range("retry.breaker_threshold", self.retry.breaker_threshold as u64, 1, 100)?;
+        validate_range("retry.breaker_cooldown_secs", self.retry.breaker_cooldown_secs, 1, 3_600)?;
+        if self.preflight.keep_alive.trim().is_empty() {
+            return Err("preflight.keep_alive must not be empty".into());
+        }
+
+        let routes = self.llm.routes.iter().map(|r| ("llm", r)).chain(self.vision.routes.iter().map(|r| ("vision", r)));
+        for (section, (call_type, route)) in routes {
+            let field = format!("{}.routes.{}", section, call_type_key(*call_type));
+            if let Some(temperature) = route.options.temperature {
+                if !(0.0..=2.0).contains(&temperature) {
+                    return Err(format!("{}.temperature must be between 0 and 2, got {}", field, temperature));
+                }
+            }
+            if let Some(num_predict) = route.options.num_predict {
+                validate_range(&format!("{}.num_predict", field), num_predict as u64, 1, 32_768)?;
+            }
+            if let Some(num_ctx) = route.options.num_ctx {
+                validate_range(&format!("{}.num_ctx", field), num_ctx as u64, 256, 1_048_576)?;
+            }
+            if route.fallback_models.iter().any(|m| m.trim().is_empty()) {
+                return Err(format!("{}.fallback_models must not contain empty names", field));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Model and options for a text LLM call. Only the route's own options
+    /// are sent, so the server defaults apply otherwise.
+    pub fn llm_route(&self, call_type: LlmCallType) -> CallRoute {
+        resolve_route(self.llm.routes.get(&call_type), &self.llm.model)
+    }
+
+    /// Model and options for a vision call. Only the route's own options
+    /// are sent, so the server defaults apply otherwise.
+    pub fn vision_route(&self, call_type: LlmCallType) -> CallRoute {
+        resolve_route(self.vision.routes.get(&call_type), &self.vision.model)
+    }
+
+    /// Backend config derived from the LLM settings
+    pub fn backend_config(&self) -> llm::BackendConfig {
+        llm::BackendConfig {
+            kind: self.llm.backend.clone(),
+            base_url: Some(self.llm.base_url.clone()).filter(|u| !u.is_empty()),
+            api_key: self.llm.api_key.clone(),
+            timeout_secs: Some(self.llm.timeout_secs),
+            keep_alive: Some(self.preflight.keep_alive.clone()),
+            model_path: Some(self.embedded_model_path().to_string_lossy().to_string()),
+        }
+    }
+
+    /// GGUF file for the embedded backend
+    pub fn embedded_model_path(&self) -> PathBuf {
+        if !self.embedded.model_path.trim().is_empty() {
+            return PathBuf::from(&self.embedded.model_path);
+        }
+
+        model_path(EMBEDDED_MODEL_FILE)
+    }
+
+    /// Directory holding the OCR models
+    pub fn ocr_model_dir(&self) -> PathBuf {
+        if !self.ocr.model_dir.trim().is_empty() {
+            return PathBuf::from(&self.ocr.model_dir);
+        }
+
+        model_path(OCR_MODEL_DIR)
+    }
+
+    /// Models used by text calls: (model, is a main model rather than a fallback)
+    pub fn text_models(&self) -> Vec<(String, bool)> {
+        self.models_for(&TEXT_CALL_TYPES, |s, ct| s.llm_route(ct))
+    }
+
+    /// Models used by vision calls, as in `text_models`
+    pub fn vision_models(&self) -> Vec<(String, bool)> {
+        self.models_for(&VISION_CALL_TYPES, |s, ct| s.vision_route(ct))
+    }
+
+    fn models_for(
+        &self,
+        call_types: &[LlmCallType],
+        route: impl Fn(&Self, LlmCallType) -> CallRoute,
+    ) -> Vec<(String, bool)> {
+        let mut models: Vec<(String, bool)> = vec![];
+        let routes: Vec<CallRoute> = call_types.iter().map(|ct| route(self, *ct)).collect();
+
+        for (model, main) in routes
+            .iter()
+            .map(|r| (&r.model, true))
+            .chain(routes.iter().flat_map(|r| r.fallback_models.iter().map(|m| (m, false))))
+        {
+            if !models.iter().any(|(m, _)| m == model) {
+                models.push((model.clone(), main));
+            }
+        }
+        models
+    }
+}
+
+/// Call types answered by the text backend
+const TEXT_CALL_TYPES: [LlmCallType; 3] = [
+    LlmCallType::Decomposition,
+    LlmCallType::ActionDecision,
+    LlmCallType::Verification,
+];
+
+/// Call types answered by the vision model (see `vision.rs`)
+const VISION_CALL_TYPES: [LlmCallType; 3] = [
+    LlmCallType::ScreenDescription,
+    LlmCallType::FindElement,
+    LlmCallType::Verification,
+];
+
+/// `route` with its model defaulted to `model`; no route means `model` with server defaults
+fn resolve_route(route: Option<&CallRoute>, model: &str) -> CallRoute {
+    match route {
+        Some(route) => CallRoute {
+            model: non_empty_or(&route.model, model),
+            options: route.options.clone(),
+            fallback_models: route.fallback_models.clone(),
+        },
+        None => CallRoute {
+            model: model.to_string(),
+            options: GenerationOptions::default(),
+            fallback_models: vec![],
+        },
+    }
+}
+
+fn non_empty_or(value: &str, fallback: &str) -> String {
+    if value.trim().is_empty() {
+        fallback.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn call_type_key(call_type: LlmCallType) -> String {
+    serde_json::to_value(call_type)
+        .ok()
+        .and_then(|v| v.as_str().map(|s| s.to_string()))
+        .unwrap_or_default()
+}
+
+fn validate_url(field: &str, url: &str) -> Result<(), String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(format!("{} must start with http:// or https://, got \"{}\"", field, url))
+    }
+}
+
+fn validate_range(field: &str, value: u64, min: u64, max: u64) -> Result<(), String> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be between {} and {}, got {}", field, min, max, value))
+    }
+}
+
+/// Upgrade a settings file written by an older version.
+/// Each step moves the JSON forward by one version.
+pub fn migrate(mut value: serde_json::Value) -> Result<serde_json::Value, String> {
+    let mut version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > SETTINGS_VERSION {
+        return Err(format!(
+            "Settings version {} is newer than this build supports ({})",
+            version, SETTINGS_VERSION
+        ));
+    }
+
+    // v0: unversioned file; fields were the same as v1
+    if version == 0 {
+        version = 1;
+    }
+
+    // v1 -> v2: per-call-type routes, empty means every call uses llm.model
+    if version == 1 {
+        value["routes"] = serde_json::json!({});
+        version = 2;
+    }
+
+    // v2 -> v3: routes move into llm.routes and vision.routes, so a text
+    // model override can't leak into vision calls of the same type
+    if version == 2 {
+        if let Some(serde_json::Value::Object(routes)) = value.as_object_mut().and_then(|v| v.remove("routes")) {
+            // v2 only read screen_description and find_element for vision calls
+            let (vision_routes, llm_routes): (serde_json::Map<_, _>, serde_json::Map<_, _>) = routes
+                .into_iter()
+                .partition(|(key, _)| key == "screen_description" || key == "find_element");
+            for (section, section_routes) in [("llm", llm_routes), ("vision", vision_routes)] {
+                if !value[section].is_object() {
+                    value[section] = serde_json::json!({});
+                }
+                value[section]["routes"] = serde_json::Value::Object(section_routes);
+            }
+        }
+        version = 3;
+    }
+
+    value["version"] = version.into();
+    Ok(value)
+}
+
+static SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+static SETTINGS_PATH: OnceLock<PathBuf> = OnceLock::new();
+static RESOURCE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Directory holding settings.json (known once `init` has run)
+pub fn config_dir() -> Option<PathBuf> {
+    SETTINGS_PATH.get().and_then(|p| p.parent()).map(|p| p.to_path_buf())
+}
+
+/// A file or directory under `models/`: the copy in the config dir wins over one bundled
+/// with the app. When neither exists, the config dir path is returned so errors name it.
+fn model_path(name: &str) -> PathBuf {
+    let installed = config_dir().map(|dir| dir.join("models").join(name));
+    let bundled = RESOURCE_DIR.get().map(|dir| dir.join("models").join(name));
+    match (installed, bundled) {
+        (Some(installed), Some(bundled)) if !installed.exists() && bundled.exists() => bundled,
+        (Some(installed), _) => installed,
+        (None, bundled) => bundled.unwrap_or_else(|| PathBuf::from("models").join(name)),
+    }
+}
+
+/// Current settings (defaults until `init` has run)
+pub fn current() -> Settings {
+    SETTINGS
+        .read()
+        .ok()
+        .and_then(|s| s.clone())
+        .unwrap_or_default()
+}
+
+/// Load settings from disk and apply them. Called once at startup.
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to find app config dir: {}", e))?;
+    let path = dir.join(SETTINGS_FILE);
+    println!("[SETTINGS] Using {}", path.display());
+    let _ = SETTINGS_PATH.set(path.clone());
+    if let Ok(resources) = app.path().resource_dir() {
+        let _ = RESOURCE_DIR.set(resources);
+    }
+
+    let settings = match load(&path) {
+        Ok(Some(settings)) => settings,
+        Ok(None) => {
+            let settings = Settings::default();
+            save(&settings)?;
+            settings
+        }
+        Err(e) => {
+            eprintln!("[SETTINGS] Warning: {}. Using defaults.", e);
+            Settings::default()
+        }
+    };
+
+    apply(settings);
+    Ok(())
+}
+
+/// Validate, persist and apply new settings
+pub fn update(settings: Settings) -> Result<Settings, String> {
+    settings.validate()?;
+    save(&settings)?;
+    apply(settings.clone());
+    Ok(settings)
+}
+
+/// Restore and persist the defaults
+pub fn reset() -> Result<Settings, String> {
+    update(Settings::default())
+}
+
+fn load(path: &PathBuf) -> Result<Option<Settings>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let value: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    let migrated = migrate(value)?;
+    let settings: Settings =
+        serde_json::from_value(migrated).map_err(|e| format!("Invalid settings: {}", e))?;
+    settings.validate()?;
+    Ok(Some(settings))
+}
+
+fn save(settings: &Settings) -> Result<(), String> {
+    let Some(path) = SETTINGS_PATH.get() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let text = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Make the settings current and rebuild what depends on them
+fn apply(settings: Settings) {
+    match llm::create_backend(&settings.backend_config()) {
+        Ok(backend) => llm::set_backend(backend),
+        Err(e) => eprintln!("[SETTINGS] Warning: {}", e),
+    }
+    match vision::create_backend(&settings.vision) {
+        Ok(backend) => vision::set_backend(backend),
+        Err(e) => eprintln!("[SETTINGS] Warning: {}", e),
+    }
+
+    if let Ok(mut slot) = SETTINGS.write() {
+        *slot = Some(settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_values() {
+        let mut settings = Settings::default();
+        settings.vision.base_url = "localhost:11434".into();
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.limits.max_goal_attempts = 0;
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.llm.model = " ".into();
+        assert!(settings.validate().is_err());
+
+        let mut settings = Settings::default();
+        settings.retry.max_backoff_ms = settings.retry.initial_backoff_ms - 1;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_migrate_unversioned_file() {
+        let value = serde_json::json!({ "llm": { "model": "qwen2.5:0.5b" } });
+        let settings: Settings = serde_json::from_value(migrate(value).unwrap()).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.llm.model, "qwen2.5:0.5b");
+        // Missing fields fall back to defaults
+        assert_eq!(settings.vision, VisionSettings::default());
+
+        assert!(migrate(serde_json::json!({ "version": SETTINGS_VERSION + 1 })).is_err());
+    }
+
+    #[test]
+    fn test_routes() {
+        let value = serde_json::json!({
+            "version": 3,
+            "llm": {
+                "model": "qwen2.5:7b",
+                "routes": {
+                    "action_decision": {
+                        "model": "qwen2.5:0.5b",
+                        "options": { "temperature": 0.0, "seed": 7 },
+                        "fallback_models": ["qwen2.5:1.5b"]
+                    },
+                    "verification": { "model": "qwen2.5:3b" }
+                }
+            },
+            "vision": { "routes": { "find_element": { "options": { "num_predict": 16 } } } }
+        });
+        let settings: Settings = serde_json::from_value(migrate(value).unwrap()).unwrap();
+        assert!(settings.validate().is_ok());
+
+        let action = settings.llm_route(LlmCallType::ActionDecision);
+        assert_eq!(action.model, "qwen2.5:0.5b");
+        assert_eq!(action.options.seed, Some(7));
+        // No output limit unless the route sets one
+        assert_eq!(action.options.num_predict, None);
+        assert_eq!(action.fallback_models, vec!["qwen2.5:1.5b".to_string()]);
+
+        assert_eq!(settings.llm_route(LlmCallType::Decomposition).model, "qwen2.5:7b");
+
+        let find = settings.vision_route(LlmCallType::FindElement);
+        assert_eq!(find.model, "llava");
+        assert_eq!(find.options.num_predict, Some(16));
+        assert_eq!(settings.vision_route(LlmCallType::ScreenDescription).options, GenerationOptions::default());
+        // A text route doesn't change the vision model for the same call type
+        assert_eq!(settings.llm_route(LlmCallType::Verification).model, "qwen2.5:3b");
+        assert_eq!(settings.vision_route(LlmCallType::Verification).model, "llava");
+
+        assert_eq!(
+            settings.text_models(),
+            vec![
+                ("qwen2.5:7b".to_string(), true),
+                ("qwen2.5:0.5b".to_string(), true),
+                ("qwen2.5:3b".to_string(), true),
+                ("qwen2.5:1.5b".to_string(), false),
+            ]
+        );
+        assert_eq!(settings.vision_models(), vec![("llava".to_string(), true)]);
+
+        let mut bad = settings.clone();
+        bad.llm.routes.get_mut(&LlmCallType::ActionDecision).unwrap().options.temperature = Some(5.0);
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_migrate_splits_routes() {
+        let value = serde_json::json!({
+            "version": 2,
+            "llm": { "model": "qwen2.5:7b" },
+            "routes": {
+                "action_decision": { "model": "qwen2.5:0.5b" },
+                "find_element": { "options": { "num_predict": 16 } }
+            }
+        });
+        let settings: Settings = serde_json::from_value(migrate(value).unwrap()).unwrap();
+        assert_eq!(settings.llm.model, "qwen2.5:7b");
+        assert_eq!(settings.llm_route(LlmCallType::ActionDecision).model, "qwen2.5:0.5b");
+        assert_eq!(settings.vision_route(LlmCallType::ActionDecision).model, "llava");
+        assert_eq!(settings.vision_route(LlmCallType::FindElement).options.num_predict, Some(16));
+        assert_eq!(settings.llm_route(LlmCallType::FindElement).options, GenerationOptions::default());
+    }
+}
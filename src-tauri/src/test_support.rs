@@ -0,0 +1,39 @@
+//! Builders and fixtures shared by unit tests
+
+use image::{EncodableLayout, ImageBuffer, PixelWithColorType};
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::vision::UIElement;
+use crate::vision::replay::ReplayVision;
+
+/// Recorded vision answers from `fixtures/vision/<name>`. Pass it to the
+/// code under test; it is not installed as the global backend.
+pub fn replay(name: &str) -> ReplayVision {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/vision").join(name);
+    ReplayVision::load(&dir).unwrap()
+}
+
+/// A fully confident element with the box (x1, y1)-(x2, y2)
+pub fn element(label: &str, kind: &str, x1: i32, y1: i32, x2: i32, y2: i32) -> UIElement {
+    UIElement {
+        label: label.into(),
+        element_type: kind.into(),
+        x1,
+        y1,
+        x2,
+        y2,
+        confidence: 1.0,
+    }
+}
+
+/// Encode `image` as PNG
+pub fn png<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> Vec<u8>
+where
+    P: PixelWithColorType,
+    [P::Subpixel]: EncodableLayout,
+{
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, image::ImageFormat::Png).unwrap();
+    buffer.into_inner()
+}
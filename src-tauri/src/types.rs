@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // ============================================
@@ -16,6 +19,7 @@ pub enum AgentState {
     Verifying,    // Checking if action succeeded
     Complete,
     Error,
+    Cancelled,    // Stopped by the user
 }
 
 impl Default for AgentState {
@@ -63,7 +67,7 @@ impl Goal {
             success_criteria,
             status: GoalStatus::Pending,
             attempts: 0,
-            max_attempts: 5,
+            max_attempts: crate::settings::current().limits.max_goal_attempts,
         }
     }
 }
@@ -72,15 +76,129 @@ impl Goal {
 // Screen Observation
 // ============================================
 
+/// What kind of control an element is. Models name kinds loosely, so
+/// synonyms ("textbox", "input") are folded into one variant; names that
+/// fit none are kept as `Unknown`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ElementKind {
+    Button,
+    TextField,
+    Menu,
+    Icon,
+    Link,
+    Tab,
+    Checkbox,
+    Text,
+    Unknown(String),
+}
+
+impl ElementKind {
+    /// Kind for a name such as "Text Field", "textbox" or "menu-item"
+    pub fn parse(name: &str) -> Self {
+        let name = name
+            .trim()
+            .to_lowercase()
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+        match name.as_str() {
+            "button" | "btn" | "push_button" => Self::Button,
+            "text_field" | "textfield" | "textbox" | "text_box" | "input" | "text_input" | "field"
+            | "search_field" | "search_box" | "searchbox" | "search_bar" | "address_bar" | "url_bar"
+            | "entry" | "textarea" | "text_area" | "combobox" | "combo_box" => Self::TextField,
+            "menu" | "menu_item" | "menuitem" | "menu_bar" | "menubar" | "dropdown" | "popup" | "select" => {
+                Self::Menu
+            }
+            "icon" | "image" | "img" | "logo" => Self::Icon,
+            "link" | "hyperlink" | "anchor" => Self::Link,
+            "tab" => Self::Tab,
+            "checkbox" | "check_box" | "switch" | "toggle" | "radio" | "radio_button" => Self::Checkbox,
+            "text" | "label" | "static_text" | "heading" | "title" => Self::Text,
+            _ => Self::Unknown(name),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Button => "button",
+            Self::TextField => "text_field",
+            Self::Menu => "menu",
+            Self::Icon => "icon",
+            Self::Link => "link",
+            Self::Tab => "tab",
+            Self::Checkbox => "checkbox",
+            Self::Text => "text",
+            Self::Unknown(name) => name,
+        }
+    }
+
+    /// The control a description names, e.g. TextField for "the search box".
+    /// Only explicit control nouns count: "text" or "title" in a description
+    /// is usually what to type or look for, not the kind of element.
+    pub fn mentioned_in(description: &str) -> Option<Self> {
+        let words: Vec<String> = description
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(String::from)
+            .collect();
+
+        words.iter().enumerate().find_map(|(i, word)| match word.as_str() {
+            "button" => Some(Self::Button),
+            // "check box" is a checkbox, any other box a text field
+            "box" if i > 0 && words[i - 1] == "check" => Some(Self::Checkbox),
+            "field" | "box" => Some(Self::TextField),
+            "menu" => Some(Self::Menu),
+            "tab" => Some(Self::Tab),
+            "checkbox" => Some(Self::Checkbox),
+            "link" => Some(Self::Link),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for ElementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for ElementKind {
+    fn from(name: &str) -> Self {
+        Self::parse(name)
+    }
+}
+
+impl From<String> for ElementKind {
+    fn from(name: String) -> Self {
+        Self::parse(&name)
+    }
+}
+
+impl From<ElementKind> for String {
+    fn from(kind: ElementKind) -> Self {
+        kind.as_str().to_string()
+    }
+}
+
 /// UI element detected by vision model with bounding box
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UIElement {
     pub label: String,
-    pub element_type: String,  // button, text_field, menu, icon, link, tab, checkbox
+    pub element_type: ElementKind,
     pub x1: i32,  // top-left x
     pub y1: i32,  // top-left y
     pub x2: i32,  // bottom-right x
     pub y2: i32,  // bottom-right y
+    /// How sure detection is of the box, 0 to 1
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
+}
+
+fn full_confidence() -> f32 {
+    1.0
 }
 
 impl UIElement {
@@ -102,6 +220,21 @@ pub struct DetectedElement {
     pub confidence: f32,
 }
 
+/// A capture as the vision model saw it
+#[derive(Clone)]
+pub struct Screenshot {
+    pub png: Vec<u8>,
+    /// Screen points per screenshot pixel
+    pub scale_x: f64,
+    pub scale_y: f64,
+}
+
+impl fmt::Debug for Screenshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Screenshot({} bytes, scale {}x{})", self.png.len(), self.scale_x, self.scale_y)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScreenState {
     pub timestamp: u64,
@@ -110,6 +243,9 @@ pub struct ScreenState {
     pub detected_elements: Vec<DetectedElement>,  // Legacy
     pub active_app: Option<String>,
     pub screenshot_hash: String,
+    /// The image the elements were found on (not sent to the UI)
+    #[serde(skip)]
+    pub screenshot: Option<Arc<Screenshot>>,
 }
 
 impl ScreenState {
@@ -126,27 +262,42 @@ impl ScreenState {
             detected_elements: vec![],
             active_app: None,
             screenshot_hash: String::new(),
+            screenshot: None,
         }
     }
 
-    /// Format UI elements as a string for the thinker
-    pub fn format_ui_elements(&self) -> String {
-        if self.ui_elements.is_empty() {
-            return "No UI elements detected".to_string();
+    /// Elements of `kind` plus text read by OCR, which has no kind of its
+    /// own (a link is just text). All elements when none is of that kind:
+    /// the model may have named it differently.
+    pub fn elements_of_kind(&self, kind: &ElementKind) -> Vec<UIElement> {
+        if !self.ui_elements.iter().any(|e| &e.element_type == kind) {
+            return self.ui_elements.clone();
         }
-
         self.ui_elements
             .iter()
-            .map(|e| {
-                let (cx, cy) = e.center();
-                format!(
-                    "- {} '{}' at ({}, {}) to ({}, {}) [center: {}, {}]",
-                    e.element_type, e.label, e.x1, e.y1, e.x2, e.y2, cx, cy
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+            .filter(|e| &e.element_type == kind || e.element_type == ElementKind::Text)
+            .cloned()
+            .collect()
+    }
+}
+
+/// One line per element, with its box and center, for the thinker
+pub fn format_elements(elements: &[UIElement]) -> String {
+    if elements.is_empty() {
+        return "No UI elements detected".to_string();
     }
+
+    elements
+        .iter()
+        .map(|e| {
+            let (cx, cy) = e.center();
+            format!(
+                "- {} '{}' at ({}, {}) to ({}, {}) [center: {}, {}]",
+                e.element_type, e.label, e.x1, e.y1, e.x2, e.y2, cx, cy
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 // ============================================
@@ -210,6 +361,8 @@ pub struct AgentSession {
     pub current_action: Option<AtomicAction>,
     pub last_observation: Option<ScreenState>,
     pub error: Option<String>,
+    /// LLM tokens and time spent so far
+    pub usage: SessionUsage,
 }
 
 impl AgentSession {
@@ -222,10 +375,11 @@ impl AgentSession {
             state: AgentState::Idle,
             action_history: vec![],
             total_actions: 0,
-            max_total_actions: 50,
+            max_total_actions: crate::settings::current().limits.max_total_actions,
             current_action: None,
             last_observation: None,
             error: None,
+            usage: SessionUsage::default(),
         }
     }
 
@@ -252,6 +406,7 @@ pub enum ActionType {
     MouseMove,
     Wait,
     FindAndClick,
+    FindImageAndClick,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -264,6 +419,8 @@ pub enum ActionParams {
     MouseMove { x: i32, y: i32 },
     Wait { ms: u64 },
     FindAndClick { element: String },
+    /// Click where the reference image (a PNG file) appears on screen
+    FindImageAndClick { image_path: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -308,7 +465,7 @@ impl From<Option<&str>> for MouseButton {
 // LLM Debug Events
 // ============================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum LlmCallType {
     Decomposition,
@@ -335,4 +492,172 @@ pub struct LlmResponseEvent {
     pub duration_ms: u64,
     pub success: bool,
     pub error: Option<String>,
+    /// Model that produced the response, if it differs from the prompt event's
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Every attempt, including retries and fallback models
+    #[serde(default)]
+    pub attempts: Vec<LlmAttempt>,
+    /// Token counts and timings, when the server reports them
+    #[serde(default)]
+    pub usage: Option<LlmUsage>,
+}
+
+/// Token counts and server-side timings for one LLM call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LlmUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Server time for the whole request, including model load
+    pub total_duration_ms: u64,
+    /// Time spent loading the model into memory
+    pub load_duration_ms: u64,
+}
+
+/// LLM usage accumulated over an agent session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionUsage {
+    pub calls: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub load_duration_ms: u64,
+    /// Wall-clock time spent waiting on LLM calls, retries included
+    pub duration_ms: u64,
+    pub by_call_type: HashMap<LlmCallType, CallTypeUsage>,
+}
+
+/// Usage for one `LlmCallType` within a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CallTypeUsage {
+    pub calls: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub load_duration_ms: u64,
+    pub duration_ms: u64,
+}
+
+impl SessionUsage {
+    /// Add one finished call. `usage` is None when the server reported nothing.
+    pub fn record(&mut self, call_type: LlmCallType, duration_ms: u64, usage: Option<&LlmUsage>) {
+        let usage = usage.copied().unwrap_or_default();
+        self.calls += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.load_duration_ms += usage.load_duration_ms;
+        self.duration_ms += duration_ms;
+
+        let entry = self.by_call_type.entry(call_type).or_default();
+        entry.calls += 1;
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+        entry.load_duration_ms += usage.load_duration_ms;
+        entry.duration_ms += duration_ms;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// How often one prompt's output failed to parse and was fixed by reprompting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepairStats {
+    /// Responses parsed, counting each repaired call once
+    pub calls: u32,
+    /// Calls whose first response failed to parse
+    pub parse_failures: u32,
+    /// Parse failures fixed by a repair prompt
+    pub repaired: u32,
+    /// Repair prompts sent
+    pub repair_attempts: u32,
+}
+
+impl RepairStats {
+    /// Share of parse failures the model fixed, if any failed
+    pub fn success_rate(&self) -> Option<f64> {
+        (self.parse_failures > 0).then(|| self.repaired as f64 / self.parse_failures as f64)
+    }
+}
+
+/// One try of an LLM call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmAttempt {
+    pub model: String,
+    /// Whether this model came from the fallback list
+    pub fallback: bool,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// Wait before the next attempt
+    pub backoff_ms: u64,
+}
+
+/// Startup check of one model, sent as `model_status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelStatusEvent {
+    pub model: String,
+    /// Ollama server the model lives on
+    pub server: String,
+    pub state: ModelState,
+    /// Pull status line, load time or error
+    pub detail: Option<String>,
+    /// Bytes downloaded so far while pulling
+    pub completed: Option<u64>,
+    pub total: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelState {
+    Checking,
+    Pulling,
+    Warming,
+    Ready,
+    Failed,
+}
+
+/// One streamed chunk of an in-flight LLM call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmTokenEvent {
+    pub call_id: String,
+    pub token: String,
+    pub index: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_kind_synonyms() {
+        assert_eq!(ElementKind::parse("textbox"), ElementKind::TextField);
+        assert_eq!(ElementKind::parse("Input"), ElementKind::TextField);
+        assert_eq!(ElementKind::parse("Text Field"), ElementKind::TextField);
+        assert_eq!(ElementKind::parse("menu-item"), ElementKind::Menu);
+        assert_eq!(ElementKind::parse("Slider"), ElementKind::Unknown("slider".into()));
+
+        // Serialized under its canonical name, read back from any synonym
+        let element: UIElement =
+            serde_json::from_str(r#"{"label":"Search","element_type":"search_box","x1":0,"y1":0,"x2":9,"y2":9}"#).unwrap();
+        assert_eq!(element.element_type, ElementKind::TextField);
+        assert!(serde_json::to_string(&element).unwrap().contains(r#""element_type":"text_field""#));
+    }
+
+    #[test]
+    fn test_elements_of_kind_keeps_text() {
+        let element = |label: &str, kind: &str| UIElement {
+            label: label.into(),
+            element_type: kind.into(),
+            x1: 0,
+            y1: 0,
+            x2: 10,
+            y2: 10,
+            confidence: 1.0,
+        };
+        let mut screen = ScreenState::new("test".into());
+        screen.ui_elements = vec![element("Home", "link"), element("Pricing", "text"), element("Go", "button")];
+
+        let labels = |elements: Vec<UIElement>| elements.into_iter().map(|e| e.label).collect::<Vec<_>>();
+        assert_eq!(labels(screen.elements_of_kind(&ElementKind::Link)), ["Home", "Pricing"]);
+        assert_eq!(labels(screen.elements_of_kind(&ElementKind::Tab)).len(), 3);
+    }
 }
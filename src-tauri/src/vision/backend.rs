@@ -0,0 +1,96 @@
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use super::ollama::OllamaVision;
+use super::replay::ReplayVision;
+use super::UIElement;
+use crate::screenshot::{self, ScreenRect};
+use crate::settings::VisionSettings;
+use crate::types::{LlmCallType, LlmUsage};
+
+/// A vision answer, parsed, with what the model actually said
+#[derive(Debug, Clone)]
+pub struct VisionReply<T> {
+    pub value: T,
+    /// The answer before parsing, for the debug overlay
+    pub raw: String,
+    pub usage: Option<LlmUsage>,
+}
+
+impl<T> VisionReply<T> {
+    pub fn new(value: T, raw: impl Into<String>) -> Self {
+        Self {
+            value,
+            raw: raw.into(),
+            usage: None,
+        }
+    }
+}
+
+/// Something that can look at screenshots: a vision model, or recordings of one.
+/// Coordinates are in screenshot pixels.
+#[async_trait]
+pub trait VisionBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Screenshot to look at, with the factors that scale its pixels back
+    /// to screen points
+    fn capture(&self) -> Result<(Vec<u8>, f64, f64), String> {
+        screenshot::capture_and_resize()
+    }
+
+    /// Screenshot at full resolution, with the factors that scale its pixels
+    /// to the coordinates `capture` scales to
+    fn capture_full(&self) -> Result<(Vec<u8>, f64, f64), String> {
+        Ok((screenshot::capture_screen_excluding_otto()?, 1.0, 1.0))
+    }
+
+    /// Capture of the screen rectangle (x1, y1)-(x2, y2), in screen points,
+    /// at full resolution, with the rectangle it covers once clipped to the screen
+    fn capture_region(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Result<(Vec<u8>, ScreenRect), String> {
+        screenshot::capture_region(x1, y1, x2, y2)
+    }
+
+    /// Clickable elements with their bounding boxes
+    async fn detect_elements(&self, image: &[u8], goal: &str) -> Result<VisionReply<Vec<UIElement>>, String>;
+
+    /// Center of the described element. None if the answer has no coordinates.
+    async fn locate(&self, image: &[u8], description: &str) -> Result<VisionReply<Option<(i32, i32)>>, String>;
+
+    /// Free-form answer to a prompt about the screenshot, from the model
+    /// routed for `call_type`
+    async fn ask(&self, image: &[u8], prompt: &str, call_type: LlmCallType) -> Result<VisionReply<String>, String>;
+}
+
+static BACKEND: RwLock<Option<Arc<dyn VisionBackend>>> = RwLock::new(None);
+
+/// Get the active vision backend, defaulting to Ollama
+pub fn backend() -> Arc<dyn VisionBackend> {
+    if let Some(backend) = BACKEND.read().ok().and_then(|b| b.clone()) {
+        return backend;
+    }
+
+    let default: Arc<dyn VisionBackend> = Arc::new(OllamaVision::default());
+    if let Ok(mut slot) = BACKEND.write() {
+        return slot.get_or_insert(default).clone();
+    }
+    default
+}
+
+/// Replace the active vision backend
+pub fn set_backend(backend: Arc<dyn VisionBackend>) {
+    println!("[VISION] Using backend: {}", backend.name());
+    if let Ok(mut slot) = BACKEND.write() {
+        *slot = Some(backend);
+    }
+}
+
+/// The vision model, or the recordings in `replay_dir` when it is set
+pub fn create_backend(settings: &VisionSettings) -> Result<Arc<dyn VisionBackend>, String> {
+    match settings.replay_dir.trim() {
+        "" => Ok(Arc::new(OllamaVision::default())),
+        dir => Ok(Arc::new(ReplayVision::load(Path::new(dir))?)),
+    }
+}
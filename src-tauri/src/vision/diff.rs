@@ -0,0 +1,204 @@
+//! Screen-change detection between two captures: a perceptual hash for the
+//! picture as a whole, and a block-wise diff for where it changed. A blinking
+//! cursor moves too few pixels in any block to count as a change.
+
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+
+/// Side of the difference hash, in bits (64 bits in all)
+const HASH_SIZE: u32 = 8;
+/// Side of the square blocks the images are compared in, in pixels
+const BLOCK: u32 = 32;
+/// A pixel changed if its gray level moved by more than this
+const PIXEL_THRESHOLD: u8 = 24;
+/// A block changed if more than this share of its pixels did
+const BLOCK_THRESHOLD: f32 = 0.05;
+
+/// A box around connected changed blocks (x1, y1 top-left, x2, y2
+/// bottom-right)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangedRegion {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+/// How two captures differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenDiff {
+    /// Share of the blocks that changed, 0 (same) to 1 (all of them)
+    pub score: f32,
+    /// Bits that differ between the perceptual hashes, 0 to 64
+    pub hash_distance: u32,
+    /// Changed areas, in pixels of the first capture
+    pub regions: Vec<ChangedRegion>,
+}
+
+impl ScreenDiff {
+    pub fn changed(&self) -> bool {
+        !self.regions.is_empty()
+    }
+
+    /// The same diff with regions multiplied by `scale_x`, `scale_y`
+    pub fn scaled(mut self, scale_x: f64, scale_y: f64) -> Self {
+        for r in &mut self.regions {
+            r.x1 = (r.x1 as f64 * scale_x) as i32;
+            r.y1 = (r.y1 as f64 * scale_y) as i32;
+            r.x2 = (r.x2 as f64 * scale_x) as i32;
+            r.y2 = (r.y2 as f64 * scale_y) as i32;
+        }
+        self
+    }
+}
+
+/// Difference hash of the PNG `image`: each bit says whether a cell of an
+/// 8x8 grid is brighter than its right neighbour. Re-encoding, small shifts
+/// and a cursor leave it unchanged; a new window doesn't.
+pub fn perceptual_hash(image: &[u8]) -> Result<u64, String> {
+    Ok(dhash(&load(image)?))
+}
+
+/// Compare the PNG captures `before` and `after`. CPU-heavy: call it from a
+/// blocking task.
+pub fn diff(before: &[u8], after: &[u8]) -> Result<ScreenDiff, String> {
+    let before = load(before)?;
+    let mut after = load(after)?;
+    let (width, height) = before.dimensions();
+    if after.dimensions() != (width, height) {
+        after = imageops::resize(&after, width, height, FilterType::Triangle);
+    }
+
+    let (columns, rows) = (width.div_ceil(BLOCK), height.div_ceil(BLOCK));
+    let mut changed = vec![false; (columns * rows) as usize];
+    for row in 0..rows {
+        for column in 0..columns {
+            let (x1, y1) = (column * BLOCK, row * BLOCK);
+            let (x2, y2) = ((x1 + BLOCK).min(width), (y1 + BLOCK).min(height));
+            let moved = (y1..y2)
+                .flat_map(|y| (x1..x2).map(move |x| (x, y)))
+                .filter(|&(x, y)| before.get_pixel(x, y)[0].abs_diff(after.get_pixel(x, y)[0]) > PIXEL_THRESHOLD)
+                .count();
+            changed[(row * columns + column) as usize] = moved as f32 > BLOCK_THRESHOLD * ((x2 - x1) * (y2 - y1)) as f32;
+        }
+    }
+
+    let score = changed.iter().filter(|c| **c).count() as f32 / changed.len().max(1) as f32;
+    let regions = regions(&changed, columns, rows)
+        .into_iter()
+        .map(|(c1, r1, c2, r2)| ChangedRegion {
+            x1: (c1 * BLOCK) as i32,
+            y1: (r1 * BLOCK) as i32,
+            x2: ((c2 + 1) * BLOCK).min(width) as i32,
+            y2: ((r2 + 1) * BLOCK).min(height) as i32,
+        })
+        .collect();
+
+    Ok(ScreenDiff {
+        score,
+        hash_distance: (dhash(&before) ^ dhash(&after)).count_ones(),
+        regions,
+    })
+}
+
+fn load(image: &[u8]) -> Result<GrayImage, String> {
+    Ok(image::load_from_memory(image)
+        .map_err(|e| format!("Failed to load screenshot: {}", e))?
+        .to_luma8())
+}
+
+fn dhash(image: &GrayImage) -> u64 {
+    let small = imageops::resize(image, HASH_SIZE + 1, HASH_SIZE, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..HASH_SIZE {
+        for x in 0..HASH_SIZE {
+            hash = hash << 1 | (small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]) as u64;
+        }
+    }
+    hash
+}
+
+/// Bounding blocks (first column, first row, last column, last row) of each
+/// group of changed blocks touching sideways or diagonally
+fn regions(changed: &[bool], columns: u32, rows: u32) -> Vec<(u32, u32, u32, u32)> {
+    let mut seen = vec![false; changed.len()];
+    let mut found = vec![];
+
+    for start in 0..changed.len() {
+        if !changed[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let (column, row) = (start as u32 % columns, start as u32 / columns);
+        let mut bounds = (column, row, column, row);
+        let mut stack = vec![(column, row)];
+
+        while let Some((column, row)) = stack.pop() {
+            bounds = (bounds.0.min(column), bounds.1.min(row), bounds.2.max(column), bounds.3.max(row));
+            for next_row in row.saturating_sub(1)..=(row + 1).min(rows - 1) {
+                for next_column in column.saturating_sub(1)..=(column + 1).min(columns - 1) {
+                    let index = (next_row * columns + next_column) as usize;
+                    if changed[index] && !seen[index] {
+                        seen[index] = true;
+                        stack.push((next_column, next_row));
+                    }
+                }
+            }
+        }
+        found.push(bounds);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::png;
+    use image::Luma;
+
+    /// A window with a toolbar and a few lines of text
+    fn screen() -> GrayImage {
+        GrayImage::from_fn(320, 200, |x, y| {
+            let shade = match y {
+                0..=23 => 200,
+                _ if (40..160).contains(&x) && y % 20 < 6 => 40,
+                _ => 245,
+            };
+            Luma([shade])
+        })
+    }
+
+    #[test]
+    fn test_cursor_blink_is_not_a_change() {
+        let before = screen();
+        let mut after = before.clone();
+        for y in 60..78 {
+            after.put_pixel(170, y, Luma([0]));
+        }
+
+        let diff = diff(&png(&before), &png(&after)).unwrap();
+        assert!(!diff.changed(), "{:?}", diff);
+        assert_eq!(diff.score, 0.0);
+        assert_eq!(perceptual_hash(&png(&before)).unwrap(), perceptual_hash(&png(&after)).unwrap());
+    }
+
+    #[test]
+    fn test_dialog_is_one_region() {
+        let before = screen();
+        let mut after = before.clone();
+        // A dialog over the text, and a badge in the toolbar corner
+        imageops::replace(&mut after, &GrayImage::from_pixel(100, 60, Luma([90])), 100, 70);
+        imageops::replace(&mut after, &GrayImage::from_pixel(12, 12, Luma([0])), 300, 4);
+
+        let diff = diff(&png(&before), &png(&after)).unwrap();
+        assert_eq!(
+            diff.regions,
+            [
+                ChangedRegion { x1: 288, y1: 0, x2: 320, y2: 32 },
+                ChangedRegion { x1: 96, y1: 64, x2: 224, y2: 160 },
+            ]
+        );
+        assert!(diff.score > 0.1 && diff.score < 0.3, "{}", diff.score);
+        assert_eq!(diff.clone().scaled(2.0, 2.0).regions[0].x2, 640);
+    }
+}
@@ -0,0 +1,181 @@
+//! Set-of-Mark prompting: candidate boxes are drawn on the screenshot with
+//! numbers, so the vision model only has to name a number instead of
+//! estimating pixel coordinates.
+
+use image::{Rgb, RgbImage};
+use regex::Regex;
+use std::io::Cursor;
+
+use super::UIElement;
+
+/// Box colors, cycled so neighbouring marks are easy to tell apart
+const COLORS: [[u8; 3]; 8] = [
+    [230, 25, 75],
+    [0, 130, 200],
+    [60, 160, 70],
+    [240, 50, 230],
+    [245, 130, 48],
+    [0, 128, 128],
+    [145, 30, 180],
+    [128, 80, 0],
+];
+const LINE_WIDTH: u32 = 2;
+/// Pixels per font dot
+const FONT_SCALE: u32 = 2;
+const LABEL_PADDING: u32 = 2;
+
+/// 3x5 bitmaps of the digits 0-9, one row per byte (bit 2 is the left column)
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Draw each box with its number (1-based, in order) on the PNG `image`.
+/// Boxes are in image pixels. Returns the marked image as PNG.
+pub fn draw_marks(image: &[u8], boxes: &[UIElement]) -> Result<Vec<u8>, String> {
+    let mut canvas = image::load_from_memory(image)
+        .map_err(|e| format!("Failed to load image: {}", e))?
+        .to_rgb8();
+
+    for (index, element) in boxes.iter().enumerate() {
+        let color = Rgb(COLORS[index % COLORS.len()]);
+        draw_box(&mut canvas, element, color);
+        draw_label(&mut canvas, element, &(index + 1).to_string(), color);
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    canvas
+        .write_to(&mut buffer, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode marked image: {}", e))?;
+    Ok(buffer.into_inner())
+}
+
+/// Prompt asking which mark is the described element
+pub fn mark_prompt(description: &str, marks: usize) -> String {
+    format!(
+        "The screenshot has {} numbered boxes drawn on it. Which box is the {}?\n\
+         Answer with ONLY the number (1-{}), or none if no box is it.\n\nAnswer:",
+        marks, description, marks
+    )
+}
+
+/// The mark index (0-based) the model picked. None when it says no box matches.
+pub fn parse_mark(response: &str, marks: usize) -> Result<Option<usize>, String> {
+    let line = response.trim().to_lowercase();
+    let number = Regex::new(r"\d+")
+        .ok()
+        .and_then(|re| re.find(&line))
+        .and_then(|m| m.as_str().parse::<usize>().ok());
+
+    match number {
+        Some(n) if (1..=marks).contains(&n) => Ok(Some(n - 1)),
+        Some(n) => Err(format!("Mark {} does not exist; there are {} marks", n, marks)),
+        None if line.contains("none") || line.contains("not") => Ok(None),
+        None => Err(format!("Expected a mark number, got: '{}'", response.trim())),
+    }
+}
+
+fn draw_box(canvas: &mut RgbImage, element: &UIElement, color: Rgb<u8>) {
+    let (width, height) = canvas.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let clamp_x = |x: i32| x.clamp(0, width as i32 - 1) as u32;
+    let clamp_y = |y: i32| y.clamp(0, height as i32 - 1) as u32;
+    let (x1, x2) = (clamp_x(element.x1.min(element.x2)), clamp_x(element.x1.max(element.x2)));
+    let (y1, y2) = (clamp_y(element.y1.min(element.y2)), clamp_y(element.y1.max(element.y2)));
+
+    for offset in 0..LINE_WIDTH {
+        for x in x1..=x2 {
+            put(canvas, x, y1 + offset, color);
+            put(canvas, x, y2.saturating_sub(offset), color);
+        }
+        for y in y1..=y2 {
+            put(canvas, x1 + offset, y, color);
+            put(canvas, x2.saturating_sub(offset), y, color);
+        }
+    }
+}
+
+/// White number on a tag in the box color, at the box's top-left corner
+fn draw_label(canvas: &mut RgbImage, element: &UIElement, text: &str, color: Rgb<u8>) {
+    let digit_width = 3 * FONT_SCALE;
+    let tag_width = text.len() as u32 * (digit_width + FONT_SCALE) - FONT_SCALE + 2 * LABEL_PADDING;
+    let tag_height = 5 * FONT_SCALE + 2 * LABEL_PADDING;
+    let left = element.x1.min(element.x2).max(0) as u32;
+    let top = element.y1.min(element.y2).max(0) as u32;
+
+    for y in top..top + tag_height {
+        for x in left..left + tag_width {
+            put(canvas, x, y, color);
+        }
+    }
+
+    let white = Rgb([255, 255, 255]);
+    for (i, digit) in text.bytes().enumerate() {
+        let bitmap = DIGITS[(digit - b'0') as usize];
+        let origin_x = left + LABEL_PADDING + i as u32 * (digit_width + FONT_SCALE);
+        let origin_y = top + LABEL_PADDING;
+        for (row, bits) in bitmap.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..FONT_SCALE {
+                    for dx in 0..FONT_SCALE {
+                        let x = origin_x + column * FONT_SCALE + dx;
+                        let y = origin_y + row as u32 * FONT_SCALE + dy;
+                        put(canvas, x, y, white);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Set a pixel, ignoring points outside the image
+fn put(canvas: &mut RgbImage, x: u32, y: u32, color: Rgb<u8>) {
+    if x < canvas.width() && y < canvas.height() {
+        canvas.put_pixel(x, y, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{element, png};
+
+    #[test]
+    fn test_draw_marks() {
+        let screen = png(&RgbImage::from_pixel(100, 60, Rgb([200, 200, 200])));
+
+        // The second box runs off the image and must be clipped, not panic
+        let boxes = [element("box", "button", 10, 10, 50, 40), element("box", "button", 60, 30, 140, 90)];
+        let marked = draw_marks(&screen, &boxes).unwrap();
+        let marked = image::load_from_memory(&marked).unwrap().to_rgb8();
+        assert_eq!(marked.dimensions(), (100, 60));
+        // Outline on the bottom edge, tag with a white digit in the corner
+        assert_eq!(marked.get_pixel(30, 40), &Rgb(COLORS[0]));
+        assert_eq!(marked.get_pixel(10 + LABEL_PADDING, 10 + LABEL_PADDING + FONT_SCALE), &Rgb([255, 255, 255]));
+        assert_eq!(marked.get_pixel(99, 45), &Rgb(COLORS[1]));
+        // Inside the box is untouched
+        assert_eq!(marked.get_pixel(30, 30), &Rgb([200, 200, 200]));
+    }
+
+    #[test]
+    fn test_parse_mark() {
+        assert_eq!(parse_mark("2", 3), Ok(Some(1)));
+        assert_eq!(parse_mark("Box 3.", 3), Ok(Some(2)));
+        assert_eq!(parse_mark("none", 3), Ok(None));
+        assert!(parse_mark("7", 3).is_err());
+        assert!(parse_mark("the blue one", 3).is_err());
+    }
+}
@@ -0,0 +1,818 @@
+mod backend;
+pub mod diff;
+mod marks;
+pub mod ocr;
+mod ollama;
+mod postprocess;
+pub mod replay;
+mod template;
+pub mod tiles;
+
+pub use backend::{backend, create_backend, set_backend, VisionBackend, VisionReply};
+
+use crate::llm::usage;
+use crate::settings::{self, CallRoute};
+use crate::types::{ElementKind, LlmCallType, LlmDebugEvent, LlmResponseEvent, Screenshot};
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Runtime};
+use uuid::Uuid;
+
+/// Vision model and options for a call type, from settings
+fn vision_route(call_type: LlmCallType) -> CallRoute {
+    settings::current().vision_route(call_type)
+}
+
+pub struct ScreenElement {
+    pub x: i32,
+    pub y: i32,
+    pub description: String,
+    /// First estimate, from the downscaled screenshot. Same as (x, y)
+    /// unless it was refined.
+    pub coarse: (i32, i32),
+    /// How sure the estimate is, 0 to 1
+    pub confidence: f32,
+}
+
+/// Confidence of a point the model gave without a box around it
+const LOCATE_CONFIDENCE: f32 = 0.6;
+/// Added when the refined point agrees with the coarse one
+const REFINE_AGREEMENT_BONUS: f32 = 0.2;
+
+impl ScreenElement {
+    fn new(x: i32, y: i32, description: &str, confidence: f32) -> Self {
+        Self {
+            x,
+            y,
+            description: description.to_string(),
+            coarse: (x, y),
+            confidence,
+        }
+    }
+}
+
+fn locate_prompt(description: &str) -> String {
+    format!(
+        "Look at this screenshot and find the {}. \
+         Output ONLY the x and y pixel coordinates of its center as two numbers separated by a comma. \
+         Example output: 640, 360",
+        description
+    )
+}
+
+fn detect_prompt(goal_context: &str) -> String {
+    format!(
+        r#"List all clickable UI elements on this screen.
+For each element, output ONE LINE in this exact format:
+TYPE | LABEL | X1, Y1, X2, Y2
+
+TYPE is one of: button, text_field, menu, icon, link, tab, checkbox
+LABEL is what the element says or does (e.g., "Submit", "Search box", "Settings icon")
+X1, Y1 is the top-left corner coordinate
+X2, Y2 is the bottom-right corner coordinate
+
+Example output:
+button | Submit | 400, 300, 500, 340
+text_field | Search box | 200, 40, 400, 60
+icon | Settings gear | 760, 20, 800, 50
+menu | File | 30, 15, 70, 35
+
+Goal: {}
+
+Elements:"#,
+        goal_context
+    )
+}
+
+fn describe_prompt(goal_context: &str) -> String {
+    format!(
+        r#"Look at this screen. Describe in 2-3 sentences:
+1. What app/window is shown
+2. Notable UI elements (buttons, text fields, menus)
+3. What could be clicked or typed
+
+Goal context: {}
+
+Description:"#,
+        goal_context
+    )
+}
+
+/// Run a vision call, recording its usage. Returns the reply and how long it took.
+async fn timed<T>(
+    call_type: LlmCallType,
+    call: impl Future<Output = Result<VisionReply<T>, String>>,
+) -> (Result<VisionReply<T>, String>, u64) {
+    let start = Instant::now();
+    let reply = call.await;
+    let duration_ms = start.elapsed().as_millis() as u64;
+    usage::record(call_type, duration_ms, reply.as_ref().ok().and_then(|r| r.usage.as_ref()));
+    (reply, duration_ms)
+}
+
+/// `timed`, also shown on the debug overlay when there is an `app_handle`
+async fn traced<R: Runtime, T>(
+    app_handle: Option<&AppHandle<R>>,
+    call_type: LlmCallType,
+    prompt: &str,
+    call: impl Future<Output = Result<VisionReply<T>, String>>,
+) -> Result<VisionReply<T>, String> {
+    let call_id = app_handle.map(|app| emit_debug_prompt(app, call_type, prompt));
+    let (reply, duration_ms) = timed(call_type, call).await;
+    if let (Some(app), Some(call_id)) = (app_handle, call_id) {
+        emit_debug_response(app, &call_id, &reply, duration_ms);
+    }
+    reply
+}
+
+/// Find `description` on the downscaled screenshot. A Set-of-Mark hit is
+/// the center of a detected box; a located point is refined on a
+/// native-resolution capture around it.
+pub(crate) async fn locate_element(vision: &dyn VisionBackend, description: &str) -> Result<ScreenElement, String> {
+    locate_element_traced(None::<&AppHandle>, vision, description).await
+}
+
+/// `locate_element`, showing each vision call on the debug overlay when
+/// there is an `app_handle`
+async fn locate_element_traced<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    vision: &dyn VisionBackend,
+    description: &str,
+) -> Result<ScreenElement, String> {
+    let settings = settings::current().vision;
+    // Capture and resize screenshot for faster processing
+    let (screenshot_bytes, scale_x, scale_y) = vision.capture()?;
+
+    if settings.set_of_mark {
+        let reply = traced(
+            app_handle,
+            LlmCallType::ScreenDescription,
+            &detect_prompt(description),
+            vision.detect_elements(&screenshot_bytes, description),
+        )
+        .await;
+        let elements = clean_elements(&screenshot_bytes, reply?.value);
+        // Boxes can miss the element; then ask for coordinates instead
+        if !elements.is_empty() {
+            let (marked, prompt) = mark_request(&screenshot_bytes, &elements, description)?;
+            let reply = traced(
+                app_handle,
+                LlmCallType::FindElement,
+                &prompt,
+                vision.ask(&marked, &prompt, LlmCallType::FindElement),
+            )
+            .await;
+            if let Some(index) = marks::parse_mark(&reply?.value, elements.len())? {
+                let (x, y) = elements[index].center();
+                println!("[VISION] '{}' is mark {} at ({}, {})", description, index + 1, x, y);
+                return Ok(ScreenElement::new(
+                    (x as f64 * scale_x) as i32,
+                    (y as f64 * scale_y) as i32,
+                    description,
+                    elements[index].confidence,
+                ));
+            }
+        }
+    }
+
+    let reply = traced(
+        app_handle,
+        LlmCallType::FindElement,
+        &locate_prompt(description),
+        vision.locate(&screenshot_bytes, description),
+    )
+    .await;
+    let element = to_screen_element(reply?, description, scale_x, scale_y)?;
+    Ok(refine_element(app_handle, vision, description, element, settings.refine_size).await)
+}
+
+/// `element` moved to where the model points on a `size` square around it
+async fn refine_element<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    vision: &dyn VisionBackend,
+    description: &str,
+    mut element: ScreenElement,
+    size: u32,
+) -> ScreenElement {
+    if let Some((x, y)) = refine(app_handle, vision, description, element.coarse, size).await {
+        println!(
+            "[VISION] Refined '{}': ({}, {}) -> ({}, {})",
+            description, element.coarse.0, element.coarse.1, x, y
+        );
+        // Two looks landing close together are more likely right
+        let size = size as i32;
+        if (x - element.x).abs() <= size / 4 && (y - element.y).abs() <= size / 4 {
+            element.confidence = (element.confidence + REFINE_AGREEMENT_BONUS).min(1.0);
+        }
+        element.x = x;
+        element.y = y;
+    }
+    element
+}
+
+/// Ask again on a native-resolution capture of the `size` square around
+/// `coarse` (screen points). None keeps the coarse estimate.
+async fn refine<R: Runtime>(
+    app_handle: Option<&AppHandle<R>>,
+    vision: &dyn VisionBackend,
+    description: &str,
+    coarse: (i32, i32),
+    size: u32,
+) -> Option<(i32, i32)> {
+    let size = size as i32;
+    if size == 0 {
+        return None;
+    }
+    let left = coarse.0 - size / 2;
+    let top = coarse.1 - size / 2;
+
+    // Near a screen edge the capture is clipped, so map through what it covers
+    let (region, (left, top, right, bottom)) = vision
+        .capture_region(left, top, left + size, top + size)
+        .map_err(|e| println!("[VISION] Refinement skipped: {}", e))
+        .ok()?;
+    let (width, height) = image::load_from_memory(&region)
+        .map_err(|e| println!("[VISION] Refinement skipped: {}", e))
+        .ok()?
+        .dimensions();
+
+    let reply = traced(
+        app_handle,
+        LlmCallType::FindElement,
+        &locate_prompt(description),
+        vision.locate(&region, description),
+    )
+    .await;
+    let (x, y) = match reply {
+        Ok(reply) => reply.value?,
+        Err(e) => {
+            println!("[VISION] Refinement skipped: {}", e);
+            return None;
+        }
+    };
+    // An answer outside the crop is a miss, not a better estimate
+    if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+        println!("[VISION] Refinement answer ({}, {}) is outside the {}x{} region", x, y, width, height);
+        return None;
+    }
+
+    // Region pixels back to screen points (the capture may be at 2x)
+    Some((
+        left + (x as f64 * (right - left) as f64 / width as f64) as i32,
+        top + (y as f64 * (bottom - top) as f64 / height as f64) as i32,
+    ))
+}
+
+/// Find the reference image at `path` on screen by template matching.
+/// Returns its center in screen points, with the match confidence.
+pub async fn find_image(path: &str) -> Result<ScreenElement, String> {
+    locate_image(backend().as_ref(), path).await
+}
+
+/// `find_image` on `vision`'s screen. Matches on the full-resolution
+/// capture, so a reference cut from a Retina screenshot keeps its size.
+pub(crate) async fn locate_image(vision: &dyn VisionBackend, path: &str) -> Result<ScreenElement, String> {
+    let file = expand_home(path);
+    let reference = std::fs::read(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let (screenshot_bytes, scale_x, scale_y) = vision.capture_full()?;
+
+    let found = tokio::task::spawn_blocking(move || template::match_template(&screenshot_bytes, &reference))
+        .await
+        .map_err(|e| format!("Template matching failed: {}", e))??
+        .ok_or_else(|| format!("{} is too large or too small to match on the screenshot", path))?;
+
+    let (x, y) = found.center();
+    if found.confidence < template::MIN_CONFIDENCE {
+        return Err(format!(
+            "Could not find {} on screen (best match {:.2} at ({}, {}))",
+            path, found.confidence, x, y
+        ));
+    }
+    println!(
+        "[VISION] Found {} at ({}, {}), scale {:.2}, confidence {:.2}",
+        path, x, y, found.scale, found.confidence
+    );
+
+    Ok(ScreenElement::new(
+        (x as f64 * scale_x) as i32,
+        (y as f64 * scale_y) as i32,
+        path,
+        found.confidence,
+    ))
+}
+
+/// `path` with a leading `~` replaced by the home directory
+fn expand_home(path: &str) -> std::path::PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => std::path::Path::new(&home).join(rest),
+        _ => std::path::PathBuf::from(path),
+    }
+}
+
+/// Number `elements` (screenshot pixels) on the screenshot. Returns the
+/// marked image and the prompt asking which mark is `description`.
+fn mark_request(image: &[u8], elements: &[UIElement], description: &str) -> Result<(Vec<u8>, String), String> {
+    let marked = marks::draw_marks(image, elements)?;
+    Ok((marked, marks::mark_prompt(description, elements.len())))
+}
+
+/// Set-of-Mark: ask the model which of `elements` (screen points, detected
+/// on `screenshot`) is `description`. Returns its index, or None if the
+/// model says none of them.
+pub async fn select_mark<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: &dyn VisionBackend,
+    screenshot: &Screenshot,
+    elements: &[crate::types::UIElement],
+    description: &str,
+) -> Result<Option<usize>, String> {
+    // Back to the pixels of the image the marks are drawn on
+    let boxes: Vec<UIElement> = elements
+        .iter()
+        .map(|e| UIElement {
+            label: e.label.clone(),
+            element_type: e.element_type.clone(),
+            x1: (e.x1 as f64 / screenshot.scale_x) as i32,
+            y1: (e.y1 as f64 / screenshot.scale_y) as i32,
+            x2: (e.x2 as f64 / screenshot.scale_x) as i32,
+            y2: (e.y2 as f64 / screenshot.scale_y) as i32,
+            confidence: e.confidence,
+        })
+        .collect();
+    let (marked, prompt) = mark_request(&screenshot.png, &boxes, description)?;
+
+    let call_id = emit_debug_prompt(app_handle, LlmCallType::FindElement, &prompt);
+    let (reply, duration_ms) =
+        timed(LlmCallType::FindElement, vision.ask(&marked, &prompt, LlmCallType::FindElement)).await;
+    emit_debug_response(app_handle, &call_id, &reply, duration_ms);
+
+    marks::parse_mark(&reply?.value, boxes.len())
+}
+
+/// Scale located coordinates back to the original screen size
+fn to_screen_element(
+    reply: VisionReply<Option<(i32, i32)>>,
+    description: &str,
+    scale_x: f64,
+    scale_y: f64,
+) -> Result<ScreenElement, String> {
+    let (x, y) = reply
+        .value
+        .ok_or_else(|| format!("Could not parse coordinates from: '{}'", reply.raw.trim()))?;
+    Ok(ScreenElement::new(
+        (x as f64 * scale_x) as i32,
+        (y as f64 * scale_y) as i32,
+        description,
+        LOCATE_CONFIDENCE,
+    ))
+}
+
+fn parse_coordinates(response: &str) -> Option<(i32, i32)> {
+    // Clean up the response - remove common prefixes
+    let cleaned = response
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit());
+
+    // Pattern 1: NUMBER, NUMBER or NUMBER NUMBER (most common)
+    let re1 = regex::Regex::new(r"(\d{1,4})[,\s]+(\d{1,4})").ok();
+    if let Some(re) = re1 {
+        if let Some(caps) = re.captures(cleaned) {
+            if let (Some(x), Some(y)) = (
+                caps.get(1).and_then(|m| m.as_str().parse().ok()),
+                caps.get(2).and_then(|m| m.as_str().parse().ok()),
+            ) {
+                return Some((x, y));
+            }
+        }
+    }
+
+    // Pattern 2: x=NUMBER y=NUMBER
+    let re2 = regex::Regex::new(r"x\s*[=:]\s*(\d+).*?y\s*[=:]\s*(\d+)").ok();
+    if let Some(re) = re2 {
+        if let Some(caps) = re.captures(response) {
+            if let (Some(x), Some(y)) = (
+                caps.get(1).and_then(|m| m.as_str().parse().ok()),
+                caps.get(2).and_then(|m| m.as_str().parse().ok()),
+            ) {
+                return Some((x, y));
+            }
+        }
+    }
+
+    // Pattern 3: (NUMBER, NUMBER)
+    let re3 = regex::Regex::new(r"\((\d+)[,\s]+(\d+)\)").ok();
+    if let Some(re) = re3 {
+        if let Some(caps) = re.captures(response) {
+            if let (Some(x), Some(y)) = (
+                caps.get(1).and_then(|m| m.as_str().parse().ok()),
+                caps.get(2).and_then(|m| m.as_str().parse().ok()),
+            ) {
+                return Some((x, y));
+            }
+        }
+    }
+
+    None
+}
+
+/// Describe the current screen state for agent observation
+pub async fn describe_screen(screenshot_bytes: &[u8], goal_context: &str) -> Result<String, String> {
+    println!("[VISION] Asking {} to describe the screen...", backend().name());
+    let prompt = describe_prompt(goal_context);
+    let (reply, _) = timed(LlmCallType::ScreenDescription, backend().ask(screenshot_bytes, &prompt, LlmCallType::ScreenDescription)).await;
+    let description = reply?.value;
+
+    println!("[VISION] Parsed response: \"{}\"", description.chars().take(100).collect::<String>());
+    Ok(description)
+}
+
+// ============================================
+// Debug versions with event emission
+// ============================================
+
+fn emit_debug_prompt<R: Runtime>(app_handle: &AppHandle<R>, call_type: LlmCallType, prompt: &str) -> String {
+    let call_id = Uuid::new_v4().to_string();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+
+    let event = LlmDebugEvent {
+        call_id: call_id.clone(),
+        call_type,
+        model: vision_route(call_type).model,
+        prompt: prompt.to_string(),
+        timestamp,
+    };
+    let _ = app_handle.emit("llm_prompt", &event);
+    call_id
+}
+
+fn emit_debug_response<R: Runtime, T>(
+    app_handle: &AppHandle<R>,
+    call_id: &str,
+    reply: &Result<VisionReply<T>, String>,
+    duration_ms: u64,
+) {
+    let (raw_response, error, usage) = match reply {
+        Ok(reply) => (reply.raw.clone(), None, reply.usage),
+        Err(e) => (String::new(), Some(e.clone()), None),
+    };
+    let event = LlmResponseEvent {
+        call_id: call_id.to_string(),
+        raw_response,
+        parsed_result: None,
+        duration_ms,
+        success: error.is_none(),
+        error,
+        model: None,
+        attempts: vec![],
+        usage,
+    };
+    let _ = app_handle.emit("llm_response", &event);
+}
+
+/// Detected UI element with bounding box
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UIElement {
+    pub label: String,
+    pub element_type: ElementKind,
+    pub x1: i32,  // top-left x
+    pub y1: i32,  // top-left y
+    pub x2: i32,  // bottom-right x
+    pub y2: i32,  // bottom-right y
+    /// Guessed from the box's shape and how it was reported, 0 to 1
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
+}
+
+fn full_confidence() -> f32 {
+    1.0
+}
+
+impl UIElement {
+    /// Get center point of the element
+    pub fn center(&self) -> (i32, i32) {
+        ((self.x1 + self.x2) / 2, (self.y1 + self.y2) / 2)
+    }
+
+    /// The box multiplied by the screenshot's scale factors
+    pub fn scaled(self, scale_x: f64, scale_y: f64) -> Self {
+        Self {
+            x1: (self.x1 as f64 * scale_x) as i32,
+            y1: (self.y1 as f64 * scale_y) as i32,
+            x2: (self.x2 as f64 * scale_x) as i32,
+            y2: (self.y2 as f64 * scale_y) as i32,
+            ..self
+        }
+    }
+}
+
+/// Detect UI elements on screen with bounding boxes
+pub async fn detect_ui_elements<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: &dyn VisionBackend,
+    screenshot_bytes: &[u8],
+    goal_context: &str,
+) -> Result<Vec<UIElement>, String> {
+    let call_id = emit_debug_prompt(app_handle, LlmCallType::ScreenDescription, &detect_prompt(goal_context));
+    let (reply, duration_ms) = timed(
+        LlmCallType::ScreenDescription,
+        vision.detect_elements(screenshot_bytes, goal_context),
+    )
+    .await;
+    emit_debug_response(app_handle, &call_id, &reply, duration_ms);
+
+    let elements = clean_elements(screenshot_bytes, reply?.value);
+    println!("[VISION] Detected {} UI elements", elements.len());
+    for elem in &elements {
+        println!("[VISION]   - {} '{}' at ({},{}) to ({},{}), confidence {:.2}", elem.element_type, elem.label, elem.x1, elem.y1, elem.x2, elem.y2, elem.confidence);
+    }
+
+    Ok(elements)
+}
+
+/// Post-process elements detected on the PNG `image`: see `postprocess`
+fn clean_elements(image: &[u8], elements: Vec<UIElement>) -> Vec<UIElement> {
+    let dimensions = image::ImageReader::new(std::io::Cursor::new(image))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    match dimensions {
+        Some((width, height)) => postprocess::postprocess(elements, width, height),
+        None => {
+            println!("[VISION] Unreadable image; boxes not checked");
+            elements
+        }
+    }
+}
+
+/// Elements on `screenshot` (downscaled by `scale`) in screen points. With
+/// `vision.tile_grid` set, full-resolution tiles are searched at the same
+/// time and their elements take the place of the overview's.
+pub async fn detect_screen_elements<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: &Arc<dyn VisionBackend>,
+    screenshot: &[u8],
+    scale: (f64, f64),
+    goal_context: &str,
+) -> Result<Vec<UIElement>, String> {
+    let settings = settings::current().vision;
+    let overview = detect_ui_elements(app_handle, vision.as_ref(), screenshot, goal_context);
+    let tiled = async {
+        if settings.tile_grid < 2 {
+            return None;
+        }
+        let detect = async {
+            let (full, full_scale_x, full_scale_y) = vision.capture_full()?;
+            tiles::detect_tiled(
+                app_handle,
+                vision.clone(),
+                full,
+                (full_scale_x, full_scale_y),
+                goal_context,
+                settings.tile_grid,
+                settings.tile_concurrency as usize,
+            )
+            .await
+        };
+        Some(detect.await)
+    };
+    let (overview, tiled) = tokio::join!(overview, tiled);
+
+    let overview: Vec<UIElement> = overview?.into_iter().map(|e| e.scaled(scale.0, scale.1)).collect();
+    match tiled {
+        Some(Ok(tiled)) => Ok(tiles::add_missing(tiled, overview)),
+        Some(Err(e)) => {
+            println!("[VISION] Tiled detection skipped: {}", e);
+            Ok(overview)
+        }
+        None => Ok(overview),
+    }
+}
+
+/// Parse UI elements from vision model response
+fn parse_ui_elements(response: &str) -> Vec<UIElement> {
+    let mut elements = Vec::new();
+
+    // Pattern: TYPE | LABEL | X1, Y1, X2, Y2
+    let re_bbox = regex::Regex::new(r"(\w+)\s*\|\s*(.+?)\s*\|\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*(\d+)").ok();
+    // Fallback pattern: TYPE | LABEL | X, Y (center point - convert to bbox with default size)
+    let re_center = regex::Regex::new(r"(\w+)\s*\|\s*(.+?)\s*\|\s*(\d+)\s*,\s*(\d+)").ok();
+
+    for line in response.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Try bounding box format first
+        if let Some(ref re) = re_bbox {
+            if let Some(caps) = re.captures(line) {
+                let element_type = ElementKind::parse(caps.get(1).map_or("", |m| m.as_str()));
+                let label = caps.get(2).map(|m| m.as_str().trim().to_string()).unwrap_or_default();
+                let x1: i32 = caps.get(3).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+                let y1: i32 = caps.get(4).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+                let x2: i32 = caps.get(5).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+                let y2: i32 = caps.get(6).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+
+                if !label.is_empty() && (x1 > 0 || y1 > 0 || x2 > 0 || y2 > 0) {
+                    elements.push(UIElement {
+                        label,
+                        element_type,
+                        x1,
+                        y1,
+                        x2,
+                        y2,
+                        confidence: 1.0,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        // Fallback: center point format (convert to bbox with default size 50x30)
+        if let Some(ref re) = re_center {
+            if let Some(caps) = re.captures(line) {
+                let element_type = ElementKind::parse(caps.get(1).map_or("", |m| m.as_str()));
+                let label = caps.get(2).map(|m| m.as_str().trim().to_string()).unwrap_or_default();
+                let cx: i32 = caps.get(3).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+                let cy: i32 = caps.get(4).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+
+                if !label.is_empty() && (cx > 0 || cy > 0) {
+                    // Convert center to bbox with default size
+                    elements.push(UIElement {
+                        label,
+                        element_type,
+                        x1: cx - 25,
+                        y1: cy - 15,
+                        x2: cx + 25,
+                        y2: cy + 15,
+                        confidence: postprocess::CENTER_ONLY_CONFIDENCE,
+                    });
+                }
+            }
+        }
+    }
+
+    elements
+}
+
+/// Find element with debug event emission, through the same Set-of-Mark
+/// and refinement steps as `locate_element`
+pub async fn find_element_with_debug<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: &dyn VisionBackend,
+    description: &str,
+) -> Result<ScreenElement, String> {
+    locate_element_traced(Some(app_handle), vision, description).await
+}
+
+/// Ask the vision model whether the screenshot `after`, taken after the
+/// last action, shows the goal achieved. `changes` says what that action
+/// changed on screen.
+pub async fn verify_goal_on_screen<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: &dyn VisionBackend,
+    after: &[u8],
+    goal: &str,
+    success_criteria: &str,
+    changes: &str,
+) -> Result<String, String> {
+    let prompt = format!(
+        r#"This screenshot was taken after the last action. Does it show the goal achieved?
+
+Goal: {}
+Success means: {}
+What the last action changed: {}
+
+Answer format:
+ACHIEVED or NOT_ACHIEVED
+PROGRESS or NO_PROGRESS
+What the screen shows (10 words max)
+
+Answer:"#,
+        goal, success_criteria, changes
+    );
+
+    let call_id = emit_debug_prompt(app_handle, LlmCallType::Verification, &prompt);
+    let (reply, duration_ms) = timed(LlmCallType::Verification, vision.ask(after, &prompt, LlmCallType::Verification)).await;
+    emit_debug_response(app_handle, &call_id, &reply, duration_ms);
+    Ok(reply?.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{png, replay};
+
+    #[tokio::test]
+    async fn test_find_element_by_mark() {
+        // Mark 1 is the address bar box, centered at (640, 52) in the 2x screenshot
+        let element = locate_element(&replay("safari"), "address bar").await.unwrap();
+        assert_eq!((element.x, element.y), (1280, 104));
+    }
+
+    #[tokio::test]
+    async fn test_select_mark_from_recording() {
+        let app = tauri::test::mock_app();
+        let vision = replay("safari");
+        let (png, scale_x, scale_y) = vision.capture().unwrap();
+        let screenshot = Screenshot { png, scale_x, scale_y };
+        // Screen points, as the observer reports them
+        let candidate = |label: &str, x1, y1, x2, y2| crate::types::UIElement {
+            label: label.into(),
+            element_type: ElementKind::TextField,
+            x1,
+            y1,
+            x2,
+            y2,
+            confidence: 1.0,
+        };
+        let elements = [candidate("Address bar", 880, 80, 1680, 128), candidate("Back", 180, 80, 220, 128)];
+
+        let mark = select_mark(app.handle(), &vision, &screenshot, &elements, "address bar").await.unwrap();
+        assert_eq!(mark, Some(0));
+        let mark = select_mark(app.handle(), &vision, &screenshot, &elements, "download button").await.unwrap();
+        assert_eq!(mark, None);
+    }
+
+    /// 1280x720 screen shot at 1/10, whose regions come back at 2x like a
+    /// Retina capture, clipped to the screen. The element is at 60% across
+    /// each region.
+    struct ZoomVision;
+
+    fn blank_png(width: u32, height: u32) -> Vec<u8> {
+        png(&image::RgbImage::new(width, height))
+    }
+
+    #[async_trait::async_trait]
+    impl VisionBackend for ZoomVision {
+        fn name(&self) -> &'static str {
+            "zoom"
+        }
+
+        fn capture(&self) -> Result<(Vec<u8>, f64, f64), String> {
+            Ok((blank_png(128, 72), 10.0, 10.0))
+        }
+
+        fn capture_region(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Result<(Vec<u8>, crate::screenshot::ScreenRect), String> {
+            let (x1, y1, x2, y2) = (x1.max(0), y1.max(0), x2.min(1280), y2.min(720));
+            Ok((blank_png(2 * (x2 - x1) as u32, 2 * (y2 - y1) as u32), (x1, y1, x2, y2)))
+        }
+
+        async fn detect_elements(&self, _image: &[u8], _goal: &str) -> Result<VisionReply<Vec<UIElement>>, String> {
+            Ok(VisionReply::new(vec![], ""))
+        }
+
+        async fn locate(&self, image: &[u8], _description: &str) -> Result<VisionReply<Option<(i32, i32)>>, String> {
+            let (width, height) = image::load_from_memory(image).unwrap().dimensions();
+            let point = if width == 128 { (50, 30) } else { (width as i32 * 6 / 10, height as i32 / 2) };
+            Ok(VisionReply::new(Some(point), ""))
+        }
+
+        async fn ask(&self, _image: &[u8], _prompt: &str, _call_type: LlmCallType) -> Result<VisionReply<String>, String> {
+            Ok(VisionReply::new("none".to_string(), ""))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refine_in_native_region() {
+        let element = locate_element(&ZoomVision, "save button").await.unwrap();
+        assert_eq!(element.coarse, (500, 300));
+        // 300pt region from (350, 150), captured at 600x600 pixels
+        let element = refine_element(None::<&AppHandle>, &ZoomVision, "save button", element, 300).await;
+        assert_eq!((element.coarse, element.x, element.y), ((500, 300), 530, 300));
+
+        // Clipped to 180pt from (1100, 150) at the right edge of the screen
+        let edge = ScreenElement::new(1250, 300, "save button", LOCATE_CONFIDENCE);
+        let edge = refine_element(None::<&AppHandle>, &ZoomVision, "save button", edge, 300).await;
+        assert_eq!((edge.x, edge.y), (1208, 300));
+    }
+
+    #[tokio::test]
+    async fn test_debug_find_matches_locate_element() {
+        let app = tauri::test::mock_app();
+        let vision = replay("safari");
+        let element = find_element_with_debug(app.handle(), &vision, "address bar").await.unwrap();
+        let expected = locate_element(&vision, "address bar").await.unwrap();
+        assert_eq!((element.x, element.y, element.coarse), (expected.x, expected.y, expected.coarse));
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_home("~/My Icons/save.png"), std::path::Path::new(&home).join("My Icons/save.png"));
+        assert_eq!(expand_home("/tmp/save.png"), std::path::PathBuf::from("/tmp/save.png"));
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        assert_eq!(parse_coordinates("640, 360"), Some((640, 360)));
+        assert_eq!(parse_coordinates("The button is at (120, 48)."), Some((120, 48)));
+        assert_eq!(parse_coordinates("I can't see it"), None);
+    }
+}
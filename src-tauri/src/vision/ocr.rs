@@ -0,0 +1,267 @@
+//! Text on the screenshot found with ocrs, a pure-Rust OCR engine that runs
+//! on the CPU from local model files. Text boxes are exact, so they sharpen
+//! the vision model's rough boxes for buttons, menu items and links.
+
+use super::UIElement;
+use crate::settings;
+use crate::types::ElementKind;
+
+/// Words further apart than this many line heights are separate elements,
+/// so a menu bar doesn't come back as one long line
+const WORD_GAP: f32 = 1.2;
+
+const DETECTION_MODEL: &str = "text-detection.rten";
+const RECOGNITION_MODEL: &str = "text-recognition.rten";
+
+/// A recognized word and its box in image pixels (x1, y1, x2, y2)
+#[derive(Debug, Clone)]
+struct Word {
+    text: String,
+    rect: [f32; 4],
+}
+
+/// Whether OCR is on in settings, built in, and has both models to load
+pub fn is_available() -> bool {
+    let settings = settings::current();
+    let dir = settings.ocr_model_dir();
+    settings.ocr.enabled
+        && cfg!(feature = "ocr")
+        && [DETECTION_MODEL, RECOGNITION_MODEL].iter().all(|file| dir.join(file).is_file())
+}
+
+/// Text elements on the PNG `image`, in image pixels. CPU-heavy: call it
+/// from a blocking task.
+pub fn detect_text(image: &[u8]) -> Result<Vec<UIElement>, String> {
+    let lines = engine::recognize(&settings::current().ocr_model_dir(), image)?;
+    let elements: Vec<UIElement> = lines.iter().flat_map(|words| group_words(words)).collect();
+    println!("[OCR] Found {} text elements", elements.len());
+    Ok(elements)
+}
+
+/// Split a line of words into phrases at wide gaps
+fn group_words(words: &[Word]) -> Vec<UIElement> {
+    let mut elements: Vec<UIElement> = vec![];
+    let mut phrase: Vec<&Word> = vec![];
+
+    for word in words.iter().filter(|w| !w.text.trim().is_empty()) {
+        if let Some(last) = phrase.last() {
+            let height = (last.rect[3] - last.rect[1]).max(word.rect[3] - word.rect[1]);
+            if word.rect[0] - last.rect[2] > height * WORD_GAP {
+                elements.extend(phrase_element(&phrase));
+                phrase.clear();
+            }
+        }
+        phrase.push(word);
+    }
+    elements.extend(phrase_element(&phrase));
+    elements
+}
+
+fn phrase_element(words: &[&Word]) -> Option<UIElement> {
+    let first = words.first()?;
+    let bounds = words.iter().fold(first.rect, |b, w| {
+        [b[0].min(w.rect[0]), b[1].min(w.rect[1]), b[2].max(w.rect[2]), b[3].max(w.rect[3])]
+    });
+    Some(UIElement {
+        label: words.iter().map(|w| w.text.trim()).collect::<Vec<_>>().join(" "),
+        element_type: ElementKind::Text,
+        x1: bounds[0].floor() as i32,
+        y1: bounds[1].floor() as i32,
+        x2: bounds[2].ceil() as i32,
+        y2: bounds[3].ceil() as i32,
+        confidence: 1.0,
+    })
+}
+
+/// Combine the vision model's elements with OCR text. A vision element
+/// whose label matches nearby text keeps its kind; if its box doesn't hold
+/// that text, it moves to the text's box. Text no element claimed is added
+/// as `Text` elements.
+pub fn merge_elements(vision: Vec<UIElement>, text: Vec<UIElement>) -> Vec<UIElement> {
+    let mut claimed = vec![false; text.len()];
+    let mut merged = Vec::with_capacity(vision.len() + text.len());
+
+    for mut element in vision {
+        let matching = text
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| !claimed[*i] && labels_match(&element.label, &t.label))
+            .min_by_key(|(_, t)| distance(element.center(), t.center()));
+
+        if let Some((index, found)) = matching {
+            claimed[index] = true;
+            if !contains(&element, found.center()) {
+                element.x1 = found.x1;
+                element.y1 = found.y1;
+                element.x2 = found.x2;
+                element.y2 = found.y2;
+            }
+        }
+        merged.push(element);
+    }
+
+    merged.extend(text.into_iter().zip(claimed).filter(|(_, c)| !c).map(|(t, _)| t));
+    merged
+}
+
+/// Same words, ignoring case and punctuation; or one label inside the other
+fn labels_match(a: &str, b: &str) -> bool {
+    let normalize = |s: &str| {
+        s.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let (short, long) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    a == b || (short.len() >= 3 && long.contains(short.as_str()))
+}
+
+fn contains(element: &UIElement, (x, y): (i32, i32)) -> bool {
+    (element.x1.min(element.x2)..=element.x1.max(element.x2)).contains(&x)
+        && (element.y1.min(element.y2)..=element.y1.max(element.y2)).contains(&y)
+}
+
+fn distance(a: (i32, i32), b: (i32, i32)) -> i64 {
+    let (dx, dy) = ((a.0 - b.0) as i64, (a.1 - b.1) as i64);
+    dx * dx + dy * dy
+}
+
+#[cfg(feature = "ocr")]
+mod engine {
+    use super::{Word, DETECTION_MODEL, RECOGNITION_MODEL};
+    use ocrs::{ImageSource, OcrEngine, OcrEngineParams, TextItem};
+    use rten::Model;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    /// Loaded engine and the directory its models came from
+    static ENGINE: Mutex<Option<(PathBuf, Arc<OcrEngine>)>> = Mutex::new(None);
+
+    /// Words of each text line, top to bottom, using the models in `model_dir`
+    pub fn recognize(model_dir: &Path, image: &[u8]) -> Result<Vec<Vec<Word>>, String> {
+        let engine = engine(model_dir)?;
+        let image = image::load_from_memory(image)
+            .map_err(|e| format!("Failed to load image: {}", e))?
+            .into_rgb8();
+        let source = ImageSource::from_bytes(image.as_raw(), image.dimensions())
+            .map_err(|e| format!("Failed to read image for OCR: {}", e))?;
+        let input = engine.prepare_input(source).map_err(|e| format!("OCR failed: {}", e))?;
+
+        let word_rects = engine.detect_words(&input).map_err(|e| format!("OCR failed: {}", e))?;
+        let line_rects = engine.find_text_lines(&input, &word_rects);
+        let lines = engine
+            .recognize_text(&input, &line_rects)
+            .map_err(|e| format!("OCR failed: {}", e))?;
+
+        Ok(lines
+            .iter()
+            .flatten()
+            .map(|line| {
+                line.words()
+                    .map(|word| {
+                        let rect = word.bounding_rect();
+                        Word {
+                            text: word.to_string(),
+                            rect: [rect.left() as f32, rect.top() as f32, rect.right() as f32, rect.bottom() as f32],
+                        }
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// The engine for `dir`, loaded on first use
+    fn engine(dir: &Path) -> Result<Arc<OcrEngine>, String> {
+        let mut slot = ENGINE.lock().map_err(|_| "OCR engine lock poisoned".to_string())?;
+        if let Some((loaded_dir, engine)) = slot.as_ref() {
+            if loaded_dir == dir {
+                return Ok(engine.clone());
+            }
+        }
+
+        println!("[OCR] Loading models from {}", dir.display());
+        let engine = OcrEngine::new(OcrEngineParams {
+            detection_model: Some(load_model(dir, DETECTION_MODEL)?),
+            recognition_model: Some(load_model(dir, RECOGNITION_MODEL)?),
+            ..Default::default()
+        })
+        .map_err(|e| format!("Failed to start OCR engine: {}", e))?;
+
+        let engine = Arc::new(engine);
+        *slot = Some((dir.to_path_buf(), engine.clone()));
+        Ok(engine)
+    }
+
+    fn load_model(dir: &Path, file: &str) -> Result<Model, String> {
+        let path = dir.join(file);
+        Model::load_file(&path).map_err(|e| {
+            format!(
+                "Failed to load OCR model {}: {}. Run scripts/download-ocr-models.sh",
+                path.display(),
+                e
+            )
+        })
+    }
+}
+
+#[cfg(not(feature = "ocr"))]
+mod engine {
+    use super::Word;
+    use std::path::Path;
+
+    pub fn recognize(_model_dir: &Path, _image: &[u8]) -> Result<Vec<Vec<Word>>, String> {
+        Err("Otto was built without the ocr feature".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::element;
+
+    fn word(text: &str, x1: f32, x2: f32) -> Word {
+        Word {
+            text: text.into(),
+            rect: [x1, 10.0, x2, 22.0],
+        }
+    }
+
+    #[test]
+    fn test_group_words() {
+        // "Save As" is one phrase; the menu item 40px away is another
+        let words = [word("Save", 0.0, 30.0), word("As", 34.0, 48.0), word("Export", 88.0, 130.0)];
+        let elements = group_words(&words);
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].label, "Save As");
+        assert_eq!((elements[0].x1, elements[0].y1, elements[0].x2, elements[0].y2), (0, 10, 48, 22));
+        assert_eq!(elements[1].label, "Export");
+    }
+
+    #[test]
+    fn test_merge_elements() {
+        let vision = vec![
+            // Box holds the text: kept as is
+            element("Sign in", "button", 100, 100, 200, 140),
+            // Box misses the text: moved onto it
+            element("Downloads", "link", 0, 0, 40, 20),
+            element("Search", "text_field", 300, 0, 500, 30),
+        ];
+        let text = vec![
+            element("Sign In", "text", 130, 112, 170, 128),
+            element("Downloads", "text", 60, 200, 140, 216),
+            element("Privacy Policy", "text", 10, 400, 120, 414),
+        ];
+
+        let merged = merge_elements(vision, text);
+        assert_eq!(merged.len(), 4);
+        assert_eq!((&merged[0].element_type, merged[0].x1), (&ElementKind::Button, 100));
+        assert_eq!((&merged[1].element_type, merged[1].x1, merged[1].y2), (&ElementKind::Link, 60, 216));
+        assert_eq!(merged[2].label, "Search");
+        assert_eq!((merged[3].label.as_str(), &merged[3].element_type), ("Privacy Policy", &ElementKind::Text));
+    }
+}
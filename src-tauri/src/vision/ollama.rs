@@ -0,0 +1,152 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::backend::{VisionBackend, VisionReply};
+use super::{detect_prompt, locate_prompt, parse_coordinates, parse_ui_elements, vision_route, UIElement};
+use crate::cancel::cancellable;
+use crate::llm::usage::OllamaMetrics;
+use crate::llm::GenerationOptions;
+use crate::settings;
+use crate::types::{LlmCallType, LlmUsage};
+
+fn vision_timeout() -> Duration {
+    Duration::from_secs(settings::current().vision.timeout_secs)
+}
+
+/// How long Ollama keeps the vision model loaded between calls
+fn keep_alive() -> String {
+    settings::current().preflight.keep_alive
+}
+
+/// URL on the Ollama server hosting the vision model
+fn ollama_url(path: &str) -> String {
+    format!("{}{}", settings::current().vision.base_url.trim_end_matches('/'), path)
+}
+
+#[derive(Serialize)]
+struct OllamaRequest {
+    model: String,
+    prompt: String,
+    images: Vec<String>,
+    stream: bool,
+    options: GenerationOptions,
+    keep_alive: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    response: String,
+    #[serde(flatten)]
+    metrics: OllamaMetrics,
+}
+
+/// The vision model on the Ollama server in settings. Model and options
+/// come from the route for each call type.
+#[derive(Default)]
+pub struct OllamaVision {
+    /// Shared across calls so connections are reused
+    client: reqwest::Client,
+}
+
+impl OllamaVision {
+
+    /// Send one prompt and image; returns the trimmed answer
+    async fn generate(
+        &self,
+        image: &[u8],
+        prompt: String,
+        call_type: LlmCallType,
+        timeout_message: &str,
+    ) -> Result<(String, Option<LlmUsage>), String> {
+        let route = vision_route(call_type);
+        let request = OllamaRequest {
+            model: route.model,
+            prompt,
+            images: vec![STANDARD.encode(image)],
+            stream: false,
+            options: route.options,
+            keep_alive: keep_alive(),
+        };
+
+        let response_text = cancellable(async {
+            let response = self
+                .client
+                .post(ollama_url("/api/generate"))
+                .timeout(vision_timeout())
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| send_error(&e, timeout_message))?;
+
+            let status = response.status();
+            let body = response.text().await
+                .map_err(|e| format!("Failed to read response body: {}", e))?;
+            if !status.is_success() {
+                return Err(format!("Ollama returned {}: {}", status, body.chars().take(500).collect::<String>()));
+            }
+            Ok(body)
+        })
+        .await?;
+
+        println!("[VISION] Raw response: {}", &response_text.chars().take(500).collect::<String>());
+
+        let ollama_response: OllamaResponse = serde_json::from_str(&response_text)
+            .map_err(|e| format!("Failed to parse Ollama response: {}. Body: {}", e, &response_text.chars().take(500).collect::<String>()))?;
+
+        Ok((ollama_response.response.trim().to_string(), ollama_response.metrics.usage()))
+    }
+}
+
+#[async_trait]
+impl VisionBackend for OllamaVision {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    async fn detect_elements(&self, image: &[u8], goal: &str) -> Result<VisionReply<Vec<UIElement>>, String> {
+        let (raw, usage) = self
+            .generate(image, detect_prompt(goal), LlmCallType::ScreenDescription, "Vision model timed out")
+            .await?;
+        Ok(VisionReply {
+            value: parse_ui_elements(&raw),
+            raw,
+            usage,
+        })
+    }
+
+    async fn locate(&self, image: &[u8], description: &str) -> Result<VisionReply<Option<(i32, i32)>>, String> {
+        let (raw, usage) = self
+            .generate(
+                image,
+                locate_prompt(description),
+                LlmCallType::FindElement,
+                "Vision model timed out. Try a simpler element description.",
+            )
+            .await?;
+        Ok(VisionReply {
+            value: parse_coordinates(&raw),
+            raw,
+            usage,
+        })
+    }
+
+    async fn ask(&self, image: &[u8], prompt: &str, call_type: LlmCallType) -> Result<VisionReply<String>, String> {
+        let (raw, usage) = self.generate(image, prompt.to_string(), call_type, "Vision model timed out").await?;
+        Ok(VisionReply {
+            value: raw.clone(),
+            raw,
+            usage,
+        })
+    }
+}
+
+/// Error message for a request that got no response
+fn send_error(e: &reqwest::Error, timeout_message: &str) -> String {
+    if e.is_timeout() {
+        timeout_message.to_string()
+    } else {
+        format!("Failed to call Ollama: {}. Is Ollama running?", e)
+    }
+}
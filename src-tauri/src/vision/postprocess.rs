@@ -0,0 +1,154 @@
+//! Cleanup of the boxes a vision model reports: corners put in order, boxes
+//! clamped to the image, empty and duplicate boxes dropped, and a confidence
+//! guessed for each, since the models don't give one.
+
+use super::UIElement;
+
+/// Boxes overlapping a more confident box by more than this (intersection
+/// over union) are duplicates of it
+const NMS_IOU: f64 = 0.5;
+/// Boxes narrower or shorter than this (pixels) after clamping are dropped
+const MIN_SIDE: i32 = 2;
+/// Confidence of an element the model gave only a center point for
+pub const CENTER_ONLY_CONFIDENCE: f32 = 0.6;
+
+/// Clean up `elements` detected on a `width` x `height` image. Kept
+/// elements stay in the model's order.
+pub fn postprocess(elements: Vec<UIElement>, width: u32, height: u32) -> Vec<UIElement> {
+    let (max_x, max_y) = (width as i32 - 1, height as i32 - 1);
+    let mut cleaned: Vec<UIElement> = vec![];
+
+    for mut element in elements {
+        let label = element.label.trim();
+        if label.is_empty() {
+            continue;
+        }
+        element.label = label.to_string();
+
+        let (x1, x2) = (element.x1.min(element.x2), element.x1.max(element.x2));
+        let (y1, y2) = (element.y1.min(element.y2), element.y1.max(element.y2));
+        let reported = area_of(x1, y1, x2, y2);
+        element.x1 = x1.clamp(0, max_x.max(0));
+        element.y1 = y1.clamp(0, max_y.max(0));
+        element.x2 = x2.clamp(0, max_x.max(0));
+        element.y2 = y2.clamp(0, max_y.max(0));
+        if element.x2 - element.x1 < MIN_SIDE || element.y2 - element.y1 < MIN_SIDE {
+            continue;
+        }
+
+        // Share of the reported box that was on the image
+        let inside = (area(&element) / reported.max(1.0)).min(1.0) as f32;
+        element.confidence = (element.confidence * inside * shape_score(&element, width, height)).clamp(0.0, 1.0);
+        cleaned.push(element);
+    }
+
+    suppress_duplicates(cleaned)
+}
+
+/// How plausible the box is as a UI control, from 0 to 1
+fn shape_score(element: &UIElement, width: u32, height: u32) -> f32 {
+    let (w, h) = ((element.x2 - element.x1) as f32, (element.y2 - element.y1) as f32);
+    let image_area = (width as f32 * height as f32).max(1.0);
+    let mut score = 1.0;
+
+    // Half the screen is a window or a hallucination, not a control
+    if w * h > image_area * 0.5 {
+        score *= 0.5;
+    }
+    // Too small to click reliably
+    if w * h < 64.0 {
+        score *= 0.7;
+    }
+    // Slivers are usually misread coordinates
+    if w.max(h) / w.min(h).max(1.0) > 25.0 {
+        score *= 0.7;
+    }
+    // Controls have short labels; sentences are descriptions of the screen
+    if element.label.split_whitespace().count() > 6 {
+        score *= 0.8;
+    }
+    score
+}
+
+/// Non-maximum suppression: of boxes that overlap too much, keep the most
+/// confident (the earliest on ties)
+fn suppress_duplicates(elements: Vec<UIElement>) -> Vec<UIElement> {
+    let mut order: Vec<usize> = (0..elements.len()).collect();
+    order.sort_by(|a, b| elements[*b].confidence.total_cmp(&elements[*a].confidence));
+
+    let mut kept: Vec<usize> = vec![];
+    for index in order {
+        if kept.iter().all(|k| iou(&elements[*k], &elements[index]) <= NMS_IOU) {
+            kept.push(index);
+        }
+    }
+    kept.sort_unstable();
+
+    let mut elements: Vec<Option<UIElement>> = elements.into_iter().map(Some).collect();
+    kept.into_iter().filter_map(|i| elements[i].take()).collect()
+}
+
+pub fn area(e: &UIElement) -> f64 {
+    area_of(e.x1, e.y1, e.x2, e.y2)
+}
+
+fn area_of(x1: i32, y1: i32, x2: i32, y2: i32) -> f64 {
+    ((x2 - x1).max(0) as f64) * ((y2 - y1).max(0) as f64)
+}
+
+/// Intersection over union of two boxes
+pub fn iou(a: &UIElement, b: &UIElement) -> f64 {
+    let intersection = area_of(a.x1.max(b.x1), a.y1.max(b.y1), a.x2.min(b.x2), a.y2.min(b.y2));
+    let union = area(a) + area(b) - intersection;
+    if union <= 0.0 {
+        0.0
+    } else {
+        intersection / union
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(label: &str, x1: i32, y1: i32, x2: i32, y2: i32, confidence: f32) -> UIElement {
+        UIElement {
+            confidence,
+            ..crate::test_support::element(label, "button", x1, y1, x2, y2)
+        }
+    }
+
+    #[test]
+    fn test_postprocess() {
+        let elements = vec![
+            // Corners swapped
+            element("OK", 140, 60, 100, 40, 1.0),
+            // Half off the right edge
+            element("Close", 300, 10, 340, 30, 1.0),
+            // The same OK button again, from a center point only
+            element("OK", 102, 38, 142, 62, CENTER_ONLY_CONFIDENCE),
+            // Zero area, then entirely outside
+            element("Line", 10, 10, 10, 50, 1.0),
+            element("Ghost", 400, 300, 450, 320, 1.0),
+            element("  ", 0, 0, 50, 50, 1.0),
+        ];
+
+        let cleaned = postprocess(elements, 320, 200);
+        assert_eq!(cleaned.iter().map(|e| e.label.as_str()).collect::<Vec<_>>(), ["OK", "Close"]);
+        assert_eq!((cleaned[0].x1, cleaned[0].y1, cleaned[0].x2, cleaned[0].y2), (100, 40, 140, 60));
+        assert_eq!(cleaned[0].confidence, 1.0);
+        assert_eq!((cleaned[1].x2, cleaned[1].y2), (319, 30));
+        assert!((cleaned[1].confidence - 0.475).abs() < 0.01, "{}", cleaned[1].confidence);
+    }
+
+    #[test]
+    fn test_nms_keeps_most_confident() {
+        let elements = vec![
+            element("Search", 10, 10, 110, 40, 0.5),
+            element("Search field", 12, 12, 112, 42, 0.9),
+            element("Go", 120, 10, 150, 40, 1.0),
+        ];
+        let cleaned = postprocess(elements, 200, 100);
+        assert_eq!(cleaned.iter().map(|e| e.label.as_str()).collect::<Vec<_>>(), ["Search field", "Go"]);
+    }
+}
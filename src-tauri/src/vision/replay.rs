@@ -0,0 +1,162 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use super::backend::{VisionBackend, VisionReply};
+use super::{parse_coordinates, parse_ui_elements, UIElement};
+use crate::screenshot::ScreenRect;
+use crate::types::LlmCallType;
+
+/// Which `VisionBackend` method an answer is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisionCall {
+    DetectElements,
+    Locate,
+    Ask,
+}
+
+/// One recorded answer from the vision model
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedResponse {
+    pub call: VisionCall,
+    /// Only answers requests containing this text (ignoring case); empty answers all
+    #[serde(default)]
+    pub query: String,
+    /// What the model said, parsed the same way as a live answer
+    pub response: String,
+}
+
+/// `fixture.json` in a replay directory
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    /// Image file next to fixture.json, as it was sent to the model
+    screenshot: String,
+    /// Screen points per screenshot pixel (x, y)
+    #[serde(default = "unscaled")]
+    scale: (f64, f64),
+    responses: Vec<RecordedResponse>,
+}
+
+fn unscaled() -> (f64, f64) {
+    (1.0, 1.0)
+}
+
+/// Plays back a recorded screenshot and vision answers, so the agent can
+/// run without a screen or a model. The first matching answer is used.
+pub struct ReplayVision {
+    screenshot: Vec<u8>,
+    scale: (f64, f64),
+    responses: Vec<RecordedResponse>,
+}
+
+impl ReplayVision {
+    /// Load `fixture.json` and its screenshot from `dir`
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join("fixture.json");
+        let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let fixture: Fixture =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid fixture {}: {}", path.display(), e))?;
+
+        let image_path = dir.join(&fixture.screenshot);
+        let screenshot =
+            fs::read(&image_path).map_err(|e| format!("Failed to read {}: {}", image_path.display(), e))?;
+        println!("[VISION] Replaying {} ({} answers)", dir.display(), fixture.responses.len());
+
+        Ok(Self {
+            screenshot,
+            scale: fixture.scale,
+            responses: fixture.responses,
+        })
+    }
+
+    fn answer(&self, call: VisionCall, query: &str) -> Result<String, String> {
+        let query = query.to_lowercase();
+        self.responses
+            .iter()
+            .find(|r| r.call == call && query.contains(&r.query.to_lowercase()))
+            .map(|r| r.response.clone())
+            .ok_or_else(|| format!("No recorded {:?} answer for '{}'", call, query))
+    }
+}
+
+#[async_trait]
+impl VisionBackend for ReplayVision {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn capture(&self) -> Result<(Vec<u8>, f64, f64), String> {
+        Ok((self.screenshot.clone(), self.scale.0, self.scale.1))
+    }
+
+    /// Only the recorded screenshot exists, at its own resolution
+    fn capture_full(&self) -> Result<(Vec<u8>, f64, f64), String> {
+        self.capture()
+    }
+
+    /// The region cut from the recorded screenshot, at its resolution
+    fn capture_region(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Result<(Vec<u8>, ScreenRect), String> {
+        let image = image::load_from_memory(&self.screenshot).map_err(|e| format!("Failed to load image: {}", e))?;
+        let to_pixels = |v: i32, scale: f64| (v.max(0) as f64 / scale) as u32;
+        let (left, top) = (to_pixels(x1.min(x2), self.scale.0), to_pixels(y1.min(y2), self.scale.1));
+        let right = to_pixels(x1.max(x2), self.scale.0).min(image.width());
+        let bottom = to_pixels(y1.max(y2), self.scale.1).min(image.height());
+        if left >= right || top >= bottom {
+            return Err("Region is outside the recorded screenshot".to_string());
+        }
+
+        let mut buffer = Cursor::new(Vec::new());
+        image
+            .crop_imm(left, top, right - left, bottom - top)
+            .write_to(&mut buffer, image::ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode region: {}", e))?;
+        let to_points = |v: u32, scale: f64| (v as f64 * scale) as i32;
+        let bounds = (
+            to_points(left, self.scale.0),
+            to_points(top, self.scale.1),
+            to_points(right, self.scale.0),
+            to_points(bottom, self.scale.1),
+        );
+        Ok((buffer.into_inner(), bounds))
+    }
+
+    async fn detect_elements(&self, _image: &[u8], goal: &str) -> Result<VisionReply<Vec<UIElement>>, String> {
+        let raw = self.answer(VisionCall::DetectElements, goal)?;
+        Ok(VisionReply::new(parse_ui_elements(&raw), raw))
+    }
+
+    async fn locate(&self, _image: &[u8], description: &str) -> Result<VisionReply<Option<(i32, i32)>>, String> {
+        let raw = self.answer(VisionCall::Locate, description)?;
+        Ok(VisionReply::new(parse_coordinates(&raw), raw))
+    }
+
+    async fn ask(&self, _image: &[u8], prompt: &str, _call_type: LlmCallType) -> Result<VisionReply<String>, String> {
+        let raw = self.answer(VisionCall::Ask, prompt)?;
+        Ok(VisionReply::new(raw.clone(), raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fixture_answers() {
+        let replay = crate::test_support::replay("safari");
+        let (image, scale_x, _) = replay.capture().unwrap();
+        assert!(image.starts_with(b"\x89PNG"));
+        assert_eq!(scale_x, 2.0);
+
+        let elements = replay.detect_elements(&image, "Open apple.com").await.unwrap().value;
+        assert_eq!(elements[0].label, "Address bar");
+
+        let found = replay.locate(&image, "the Address Bar").await.unwrap();
+        assert_eq!(found.value, Some((640, 52)));
+        let missing = replay.locate(&image, "download button").await.unwrap();
+        assert_eq!(missing.value, None);
+        assert!(replay.locate(&image, "trash").await.is_err());
+    }
+}
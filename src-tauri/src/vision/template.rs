@@ -0,0 +1,379 @@
+//! Finding a reference image (an icon, a toolbar button) on the screenshot
+//! with normalized cross-correlation, at several scales since the image may
+//! come from a screen with a different resolution.
+
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Matches below this confidence aren't clicked
+pub const MIN_CONFIDENCE: f32 = 0.8;
+
+/// Template scales tried, from 1/4 to 2x in steps of 2^(1/5)
+const SCALE_STEPS: std::ops::RangeInclusive<i32> = -10..=5;
+/// Scaled templates smaller than this (pixels per side) are skipped
+const MIN_SIDE: u32 = 8;
+/// Positions are ranked on a copy of the screenshot halved until its
+/// longer side is at most this long, then refined at full size
+const FRAME_SIDE: usize = 1600;
+/// Large templates are ranked on copies halved further, until the
+/// template's shorter side is about this long
+const COARSE_SIDE: f32 = 8.0;
+/// Templates shrunk below this (pixels per side) are too blurred to rank
+const MIN_COARSE_SIDE: usize = 4;
+/// Best coarse positions refined at full size, per scale
+const CANDIDATES: usize = 3;
+
+/// Where the template matched, in screenshot pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateMatch {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Template size on the screenshot relative to the reference image
+    pub scale: f32,
+    /// Normalized cross-correlation, 0 (no likeness) to 1 (identical)
+    pub confidence: f32,
+}
+
+impl TemplateMatch {
+    pub fn center(&self) -> (i32, i32) {
+        ((self.x + self.width / 2) as i32, (self.y + self.height / 2) as i32)
+    }
+}
+
+/// Best match of the PNG `template` on the PNG `image`, whatever its
+/// confidence. None when the template doesn't fit at any scale.
+/// CPU-heavy: call it from a blocking task.
+pub fn match_template(image: &[u8], template: &[u8]) -> Result<Option<TemplateMatch>, String> {
+    let image = image::load_from_memory(image)
+        .map_err(|e| format!("Failed to load screenshot: {}", e))?
+        .to_luma8();
+    let template = image::load_from_memory(template)
+        .map_err(|e| format!("Failed to load template image: {}", e))?
+        .to_luma8();
+    if Prepared::new(&template).is_none() {
+        return Err("Template image is a single flat color".to_string());
+    }
+
+    let mut pyramid = Pyramid::new(&image);
+    let mut best: Option<TemplateMatch> = None;
+
+    for step in SCALE_STEPS {
+        let scale = 2f32.powf(step as f32 / 5.0);
+        let width = (template.width() as f32 * scale).round() as u32;
+        let height = (template.height() as f32 * scale).round() as u32;
+        if width.min(height) < MIN_SIDE || width > image.width() || height > image.height() {
+            continue;
+        }
+
+        let scaled = imageops::resize(&template, width, height, FilterType::Triangle);
+        let Some(((x, y), score)) = match_at_scale(&mut pyramid, &scaled) else {
+            continue;
+        };
+        if best.is_none_or(|b| score > b.confidence) {
+            best = Some(TemplateMatch {
+                x,
+                y,
+                width,
+                height,
+                scale,
+                confidence: score.clamp(0.0, 1.0),
+            });
+        }
+    }
+
+    Ok(best)
+}
+
+/// Best position and score for a template already at its final size
+fn match_at_scale(pyramid: &mut Pyramid, template: &GrayImage) -> Option<((u32, u32), f32)> {
+    let prepared = Prepared::new(template)?;
+    let side = template.width().min(template.height()) as f32;
+    let level = ((side / COARSE_SIDE).log2().floor().max(0.0) as usize).max(pyramid.frame_level);
+    if level == 0 {
+        let full = pyramid.level(0);
+        return full.best_in(&prepared, 0..=full.width - prepared.width, 0..=full.height - prepared.height);
+    }
+
+    // Rank positions on the shrunk screenshot, then search around the best few
+    let factor = 1usize << level;
+    let shrink = |length: u32| ((length as f32 / factor as f32).round() as u32).max(1);
+    let coarse_template =
+        Prepared::new(&imageops::resize(template, shrink(template.width()), shrink(template.height()), FilterType::Triangle))?;
+    let coarse = pyramid.level(level);
+    if coarse_template.width.min(coarse_template.height) < MIN_COARSE_SIDE
+        || coarse_template.width > coarse.width
+        || coarse_template.height > coarse.height
+    {
+        return None;
+    }
+    let positions = coarse.top_positions(&coarse_template, CANDIDATES);
+
+    let full = pyramid.level(0);
+    let radius = factor * 2;
+    positions
+        .into_iter()
+        .filter_map(|(x, y)| {
+            let (cx, cy) = (x * factor, y * factor);
+            let xs = cx.saturating_sub(radius).min(full.width - prepared.width)..=(cx + radius).min(full.width - prepared.width);
+            let ys = cy.saturating_sub(radius).min(full.height - prepared.height)..=(cy + radius).min(full.height - prepared.height);
+            full.best_in(&prepared, xs, ys)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Template pixels minus their mean, with the norm of the result
+struct Prepared {
+    width: usize,
+    height: usize,
+    centered: Vec<f32>,
+    norm: f64,
+}
+
+impl Prepared {
+    /// None for a flat template, which correlates with nothing
+    fn new(template: &GrayImage) -> Option<Self> {
+        let pixels: Vec<f32> = template.pixels().map(|p| p[0] as f32).collect();
+        let mean = pixels.iter().sum::<f32>() / pixels.len().max(1) as f32;
+        let centered: Vec<f32> = pixels.iter().map(|p| p - mean).collect();
+        let norm = centered.iter().map(|p| (*p as f64).powi(2)).sum::<f64>().sqrt();
+        (norm > 1e-3).then(|| Self {
+            width: template.width() as usize,
+            height: template.height() as usize,
+            centered,
+            norm,
+        })
+    }
+}
+
+/// The screenshot and copies of it halved again and again, made as needed.
+/// Level k is 2^k times smaller.
+struct Pyramid {
+    levels: Vec<Plane>,
+    /// First level no longer than `FRAME_SIDE`, where coarse search starts
+    frame_level: usize,
+}
+
+impl Pyramid {
+    fn new(image: &GrayImage) -> Self {
+        let longest = image.width().max(image.height()) as usize;
+        let mut frame_level = 0;
+        while longest >> frame_level > FRAME_SIDE {
+            frame_level += 1;
+        }
+        Self {
+            levels: vec![Plane::new(image)],
+            frame_level,
+        }
+    }
+
+    fn level(&mut self, level: usize) -> &Plane {
+        while self.levels.len() <= level {
+            let next = self.levels[self.levels.len() - 1].half();
+            self.levels.push(next);
+        }
+        &self.levels[level]
+    }
+}
+
+/// Grayscale pixels as floats
+struct Plane {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+}
+
+/// A position and its score, ordered by score
+#[derive(Clone, Copy)]
+struct Peak {
+    score: f32,
+    x: usize,
+    y: usize,
+}
+
+impl PartialEq for Peak {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Peak {}
+
+impl PartialOrd for Peak {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Peak {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score)
+    }
+}
+
+impl Plane {
+    fn new(image: &GrayImage) -> Self {
+        Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels: image.pixels().map(|p| p[0] as f32).collect(),
+        }
+    }
+
+    /// Each pixel the average of a 2x2 block, so pixel (x, y) covers
+    /// (2x, 2y) in this plane
+    fn half(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let at = |x: usize, y: usize| self.pixels[y.min(self.height - 1) * self.width + x.min(self.width - 1)];
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| (at(2 * x, 2 * y) + at(2 * x + 1, 2 * y) + at(2 * x, 2 * y + 1) + at(2 * x + 1, 2 * y + 1)) / 4.0)
+            .collect();
+        Self { width, height, pixels }
+    }
+
+    /// Correlation of the template with the window at (x, y)
+    fn score(&self, template: &Prepared, x: usize, y: usize) -> f32 {
+        let (mut sum, mut squares, mut dot) = (0.0f64, 0.0f64, 0.0f64);
+        for row in 0..template.height {
+            let window = &self.pixels[(y + row) * self.width + x..][..template.width];
+            let line = &template.centered[row * template.width..][..template.width];
+            let (mut row_sum, mut row_squares, mut row_dot) = (0.0f32, 0.0f32, 0.0f32);
+            for (a, b) in window.iter().zip(line) {
+                row_sum += a;
+                row_squares += a * a;
+                row_dot += a * b;
+            }
+            sum += row_sum as f64;
+            squares += row_squares as f64;
+            dot += row_dot as f64;
+        }
+
+        let n = (template.width * template.height) as f64;
+        let variance = squares - sum * sum / n;
+        if variance <= 1e-6 * n {
+            return 0.0;
+        }
+        (dot / (variance.sqrt() * template.norm)) as f32
+    }
+
+    fn best_in(
+        &self,
+        template: &Prepared,
+        xs: std::ops::RangeInclusive<usize>,
+        ys: std::ops::RangeInclusive<usize>,
+    ) -> Option<((u32, u32), f32)> {
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| ((x as u32, y as u32), self.score(template, x, y)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Highest-scoring local peaks, at least half a template apart
+    fn top_positions(&self, template: &Prepared, count: usize) -> Vec<(usize, usize)> {
+        let (columns, rows) = (self.width - template.width + 1, self.height - template.height + 1);
+        let scores: Vec<f32> = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .map(|(x, y)| self.score(template, x, y))
+            .collect();
+
+        // Only the best few peaks are kept, in a min-heap of fixed size
+        let capacity = count * 4;
+        let mut heap: BinaryHeap<Reverse<Peak>> = BinaryHeap::with_capacity(capacity + 1);
+        for y in 0..rows {
+            for x in 0..columns {
+                let score = scores[y * columns + x];
+                // Of equal neighbors, only the first in scan order is a peak
+                let is_peak = (y.saturating_sub(1)..=(y + 1).min(rows - 1)).all(|ny| {
+                    (x.saturating_sub(1)..=(x + 1).min(columns - 1)).all(|nx| {
+                        let neighbor = scores[ny * columns + nx];
+                        neighbor < score || (neighbor == score && (ny, nx) >= (y, x))
+                    })
+                });
+                if !is_peak || heap.len() == capacity && heap.peek().is_some_and(|Reverse(low)| low.score >= score) {
+                    continue;
+                }
+                heap.push(Reverse(Peak { score, x, y }));
+                if heap.len() > capacity {
+                    heap.pop();
+                }
+            }
+        }
+
+        let spacing = template.width.max(template.height) / 2;
+        let mut top: Vec<(usize, usize)> = vec![];
+        for Reverse(Peak { x, y, .. }) in heap.into_sorted_vec() {
+            if top.len() == count {
+                break;
+            }
+            if top.iter().all(|(tx, ty)| tx.abs_diff(x) > spacing || ty.abs_diff(y) > spacing) {
+                top.push((x, y));
+            }
+        }
+        top
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::png;
+    use image::Luma;
+
+    /// Noisy background so the icon is the only thing that correlates
+    fn screen() -> GrayImage {
+        noise(200, 120)
+    }
+
+    fn noise(width: u32, height: u32) -> GrayImage {
+        let mut seed: u32 = 7;
+        GrayImage::from_fn(width, height, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            Luma([100 + (seed >> 16) as u8 % 40])
+        })
+    }
+
+    /// A ring with a bar through it
+    fn icon() -> GrayImage {
+        GrayImage::from_fn(24, 24, |x, y| {
+            let d = ((x as f32 - 11.5).powi(2) + (y as f32 - 11.5).powi(2)).sqrt();
+            Luma([if (7.0..11.0).contains(&d) || (10..14).contains(&y) { 20 } else { 240 }])
+        })
+    }
+
+    #[test]
+    fn test_match_scaled_icon() {
+        let mut screen = screen();
+        // Drawn 1.5x larger than the reference, as on a denser screen
+        let drawn = imageops::resize(&icon(), 36, 36, FilterType::Triangle);
+        imageops::replace(&mut screen, &drawn, 120, 50);
+
+        let found = match_template(&png(&screen), &png(&icon())).unwrap().unwrap();
+        assert!(found.confidence > 0.9, "{:?}", found);
+        let (x, y) = found.center();
+        assert!((x - 138).abs() <= 3 && (y - 68).abs() <= 3, "{:?}", found);
+        assert!((found.scale - 1.5).abs() < 0.1, "{:?}", found);
+    }
+
+    #[test]
+    fn test_match_on_wide_screen() {
+        // Wider than FRAME_SIDE, so positions are only ranked on a halved copy
+        let mut screen = noise(1700, 300);
+        imageops::replace(&mut screen, &icon(), 1301, 203);
+
+        let found = match_template(&png(&screen), &png(&icon())).unwrap().unwrap();
+        assert!(found.confidence > 0.95, "{:?}", found);
+        assert_eq!((found.x, found.y, found.width), (1301, 203, 24));
+    }
+
+    #[test]
+    fn test_missing_icon_has_low_confidence() {
+        let found = match_template(&png(&screen()), &png(&icon())).unwrap().unwrap();
+        assert!(found.confidence < MIN_CONFIDENCE, "{:?}", found);
+
+        let flat = GrayImage::from_pixel(16, 16, Luma([128]));
+        assert!(match_template(&png(&screen()), &png(&flat)).is_err());
+    }
+}
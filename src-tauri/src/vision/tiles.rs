@@ -0,0 +1,220 @@
+//! Tiled detection: the full-resolution capture is cut into overlapping
+//! tiles so small icons and menu-bar items keep enough pixels for the
+//! vision model to see them.
+
+use image::{DynamicImage, GenericImageView};
+use std::io::Cursor;
+use std::sync::Arc;
+use tauri::{AppHandle, Runtime};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::postprocess::{area, iou};
+use super::{detect_ui_elements, UIElement, VisionBackend};
+
+/// Fraction of a tile shared with its neighbour, so elements on a seam are
+/// whole in at least one tile
+const OVERLAP: f64 = 0.1;
+/// Tiles bigger than this are shrunk before they are sent
+const MAX_TILE_WIDTH: u32 = 1280;
+const MAX_TILE_HEIGHT: u32 = 720;
+/// Boxes overlapping more than this (intersection over union) are one element
+const DUPLICATE_IOU: f64 = 0.5;
+
+/// A rectangle of the full capture, in its pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// `grid` x `grid` overlapping tiles covering a `width` x `height` image
+fn tile_grid(width: u32, height: u32, grid: u32) -> Vec<Tile> {
+    let spans = |length: u32| -> Vec<(u32, u32)> {
+        let n = grid as f64;
+        let size = ((length as f64 / (n - (n - 1.0) * OVERLAP)).ceil() as u32).min(length);
+        let step = size as f64 * (1.0 - OVERLAP);
+        (0..grid)
+            .map(|i| (((i as f64 * step) as u32).min(length - size), size))
+            .collect()
+    };
+
+    let mut tiles = vec![];
+    for (y, tile_height) in spans(height) {
+        for (x, tile_width) in spans(width) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_width,
+                height: tile_height,
+            });
+        }
+    }
+    tiles
+}
+
+/// PNG of one tile, shrunk to fit the model's input, with the factors that
+/// scale its pixels back to the full capture
+fn cut_tile(image: &DynamicImage, tile: Tile) -> Result<(Vec<u8>, f64, f64), String> {
+    let mut crop = image.crop_imm(tile.x, tile.y, tile.width, tile.height);
+    if tile.width > MAX_TILE_WIDTH || tile.height > MAX_TILE_HEIGHT {
+        crop = crop.resize(MAX_TILE_WIDTH, MAX_TILE_HEIGHT, image::imageops::FilterType::Triangle);
+    }
+    let (width, height) = crop.dimensions();
+
+    let mut buffer = Cursor::new(Vec::new());
+    crop.write_to(&mut buffer, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode tile: {}", e))?;
+    Ok((
+        buffer.into_inner(),
+        tile.width as f64 / width as f64,
+        tile.height as f64 / height as f64,
+    ))
+}
+
+/// Detect elements on `grid` x `grid` tiles of the full capture `image`,
+/// at most `concurrency` at a time. `scale` takes capture pixels to screen
+/// points; the elements returned are in screen points, de-duplicated.
+pub async fn detect_tiled<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    vision: Arc<dyn VisionBackend>,
+    image: Vec<u8>,
+    scale: (f64, f64),
+    goal_context: &str,
+    grid: u32,
+    concurrency: usize,
+) -> Result<Vec<UIElement>, String> {
+    let tiles = tokio::task::spawn_blocking(move || -> Result<Vec<_>, String> {
+        let image = image::load_from_memory(&image).map_err(|e| format!("Failed to load image: {}", e))?;
+        let (width, height) = image.dimensions();
+        tile_grid(width, height, grid)
+            .into_iter()
+            .map(|tile| cut_tile(&image, tile).map(|png| (tile, png)))
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to cut tiles: {}", e))??;
+    println!("[VISION] Detecting on {} tiles, {} at a time", tiles.len(), concurrency);
+
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, (tile, (png, tile_scale_x, tile_scale_y))) in tiles.into_iter().enumerate() {
+        let (app_handle, vision, permits) = (app_handle.clone(), vision.clone(), permits.clone());
+        let goal_context = goal_context.to_string();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let elements = detect_ui_elements(&app_handle, vision.as_ref(), &png, &goal_context).await;
+            // Tile pixels -> capture pixels -> screen points
+            let to_screen = |v: i32, tile_scale: f64, offset: u32, screen_scale: f64| {
+                ((offset as f64 + v as f64 * tile_scale) * screen_scale) as i32
+            };
+            let elements = elements.map(|elements| {
+                elements
+                    .into_iter()
+                    .map(|e| UIElement {
+                        x1: to_screen(e.x1, tile_scale_x, tile.x, scale.0),
+                        y1: to_screen(e.y1, tile_scale_y, tile.y, scale.1),
+                        x2: to_screen(e.x2, tile_scale_x, tile.x, scale.0),
+                        y2: to_screen(e.y2, tile_scale_y, tile.y, scale.1),
+                        ..e
+                    })
+                    .collect::<Vec<_>>()
+            });
+            (index, elements)
+        });
+    }
+
+    let mut results = tasks.join_all().await;
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut elements = vec![];
+    let mut last_error = None;
+    let tile_count = results.len();
+    for (index, result) in results {
+        match result {
+            Ok(found) => elements.extend(found),
+            Err(e) => {
+                println!("[VISION] Tile {} failed: {}", index, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    if let Some(e) = last_error.filter(|_| elements.is_empty()) {
+        return Err(format!("All {} tiles failed: {}", tile_count, e));
+    }
+
+    let elements = dedupe(elements);
+    println!("[VISION] {} elements after merging tiles", elements.len());
+    Ok(elements)
+}
+
+/// Drop elements found twice where tiles overlap, keeping the bigger box
+/// (the other is usually cut off by a tile edge)
+fn dedupe(elements: Vec<UIElement>) -> Vec<UIElement> {
+    let mut kept: Vec<UIElement> = Vec::with_capacity(elements.len());
+    for element in elements {
+        match kept.iter_mut().find(|k| is_duplicate(k, &element)) {
+            Some(existing) if area(&element) > area(existing) => *existing = element,
+            Some(_) => {}
+            None => kept.push(element),
+        }
+    }
+    kept
+}
+
+/// Add the elements of `extra` that aren't already in `elements`
+pub fn add_missing(mut elements: Vec<UIElement>, extra: Vec<UIElement>) -> Vec<UIElement> {
+    for element in extra {
+        if !elements.iter().any(|e| is_duplicate(e, &element)) {
+            elements.push(element);
+        }
+    }
+    elements
+}
+
+/// Mostly the same box, or the same label with one box inside the other
+fn is_duplicate(a: &UIElement, b: &UIElement) -> bool {
+    let contains = |outer: &UIElement, (x, y): (i32, i32)| outer.x1 <= x && x <= outer.x2 && outer.y1 <= y && y <= outer.y2;
+    iou(a, b) > DUPLICATE_IOU
+        || (a.label.eq_ignore_ascii_case(&b.label) && (contains(a, b.center()) || contains(b, a.center())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon(label: &str, x1: i32, y1: i32, x2: i32, y2: i32) -> UIElement {
+        crate::test_support::element(label, "icon", x1, y1, x2, y2)
+    }
+
+    #[test]
+    fn test_tile_grid_covers_image_with_overlap() {
+        let tiles = tile_grid(5120, 2880, 3);
+        assert_eq!(tiles.len(), 9);
+        assert_eq!(tiles[0], Tile { x: 0, y: 0, width: 1829, height: 1029 });
+        // Last tile ends at the edge, and neighbours share a strip
+        let last = tiles[8];
+        assert_eq!((last.x + last.width, last.y + last.height), (5120, 2880));
+        assert!(tiles[1].x < tiles[0].x + tiles[0].width);
+        assert_eq!(tile_grid(100, 50, 1), vec![Tile { x: 0, y: 0, width: 100, height: 50 }]);
+    }
+
+    #[test]
+    fn test_dedupe_keeps_whole_box() {
+        let elements = vec![
+            // Wi-Fi icon cut by the tile edge, then whole in the next tile
+            icon("Wi-Fi", 1800, 4, 1812, 30),
+            icon("Wi-Fi", 1800, 4, 1830, 30),
+            icon("Battery", 1840, 4, 1880, 30),
+        ];
+        let merged = dedupe(elements);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].x2, 1830);
+
+        let overview = vec![icon("wi-fi", 1798, 2, 1832, 32), icon("Clock", 1900, 4, 2000, 30)];
+        let merged = add_missing(merged, overview);
+        assert_eq!(merged.iter().map(|e| e.label.as_str()).collect::<Vec<_>>(), ["Wi-Fi", "Battery", "Clock"]);
+    }
+}
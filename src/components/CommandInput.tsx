@@ -37,6 +37,8 @@ export function CommandInput({ disabled }: Props) {
         await invoke('plan_command', { command: input })
       }
     } catch (err) {
+      // A cancelled session already reported its state
+      if (String(err) === 'Cancelled') return
       console.error('Failed to execute command:', err)
       setError(String(err))
     }
@@ -3,7 +3,7 @@ import { getCurrentWindow } from '@tauri-apps/api/window'
 import { useOttoStore } from '../store/otto'
 import { useTauriEvents } from '../hooks/useTauriEvents'
 import { CommandInput } from './CommandInput'
-import type { LlmCallType } from '../types'
+import type { CallTypeUsage, LlmCallType } from '../types'
 
 // Format call type for display
 function formatCallType(type: LlmCallType): string {
@@ -21,7 +21,7 @@ export function Overlay() {
   useTauriEvents()
   const {
     state, agentSession, agentSessionV2, useAgentMode, useAgentV2,
-    llmCalls, selectedLlmCall, selectLlmCall, clearLlmCalls
+    llmCalls, selectedLlmCall, selectLlmCall, clearLlmCalls, modelStatus
   } = useOttoStore()
 
   const [expandedPrompt, setExpandedPrompt] = useState(false)
@@ -35,6 +35,11 @@ export function Overlay() {
     await getCurrentWindow().startDragging()
   }, [])
 
+  // Models not ready yet (ready ones drop out of the list)
+  const pendingModels = Object.values(modelStatus).filter((m) => m.state !== 'ready')
+
+  const sessionUsage = useAgentV2 ? agentSessionV2?.usage : agentSession?.usage
+
   // Get sorted LLM calls by timestamp
   const sortedLlmCalls = Object.values(llmCalls).sort((a, b) => a.timestamp - b.timestamp)
   const selectedCall = selectedLlmCall ? llmCalls[selectedLlmCall] : null
@@ -48,6 +53,17 @@ export function Overlay() {
           <button className="btn-clear" onClick={clearLlmCalls}>Clear</button>
         )}
       </div>
+      {sessionUsage && sessionUsage.calls > 0 && (
+        <div className="llm-usage-summary">
+          {sessionUsage.prompt_tokens + sessionUsage.completion_tokens} tok · {sessionUsage.duration_ms}ms
+          {sessionUsage.load_duration_ms > 0 && ` · ${sessionUsage.load_duration_ms}ms load`}
+          {(Object.entries(sessionUsage.by_call_type) as [LlmCallType, CallTypeUsage][])
+            .sort(([, a], [, b]) => b.duration_ms - a.duration_ms)
+            .map(([type, usage]) => (
+              <div key={type}>{formatCallType(type)}: {usage.duration_ms}ms</div>
+            ))}
+        </div>
+      )}
       <div className="llm-list-items">
         {sortedLlmCalls.map((call) => (
           <div
@@ -94,6 +110,12 @@ export function Overlay() {
           {selectedCall.duration_ms && (
             <span className="call-duration">{selectedCall.duration_ms}ms</span>
           )}
+          {selectedCall.usage && (
+            <span className="call-duration">
+              {selectedCall.usage.prompt_tokens}+{selectedCall.usage.completion_tokens} tok
+              {selectedCall.usage.load_duration_ms > 0 && `, ${selectedCall.usage.load_duration_ms}ms load`}
+            </span>
+          )}
           <span className={`call-status-badge ${selectedCall.status}`}>
             {selectedCall.status}
           </span>
@@ -153,6 +175,28 @@ export function Overlay() {
           </div>
         )}
 
+        {/* Parsed JSON, with any repairs it needed */}
+        {selectedCall.parsed_result && (
+          <div className="detail-section">
+            <div className="section-header">Parsed</div>
+            <pre className="detail-content">{selectedCall.parsed_result}</pre>
+          </div>
+        )}
+
+        {/* Attempts (only shown when a retry or fallback happened) */}
+        {selectedCall.attempts && selectedCall.attempts.length > 1 && (
+          <div className="detail-section">
+            <div className="section-header">Attempts</div>
+            <pre className="detail-content">
+              {selectedCall.attempts.map((a, i) =>
+                `${i + 1}. ${a.model}${a.fallback ? ' (fallback)' : ''} ${a.duration_ms}ms` +
+                (a.error ? ` - ${a.error}` : ' - ok') +
+                (a.backoff_ms ? `, retry in ${a.backoff_ms}ms` : '')
+              ).join('\n')}
+            </pre>
+          </div>
+        )}
+
         {/* Error */}
         {selectedCall.error && (
           <div className="detail-section error">
@@ -172,10 +216,27 @@ export function Overlay() {
       <CommandInput disabled={
         state === 'planning' ||
         state === 'executing' ||
-        (useAgentV2 && agentSessionV2 !== null && agentSessionV2.state !== 'done' && agentSessionV2.state !== 'failed') ||
-        (useAgentMode && !useAgentV2 && agentSession !== null && agentSession.state !== 'complete' && agentSession.state !== 'error')
+        (useAgentV2 && agentSessionV2 !== null && agentSessionV2.state !== 'done' && agentSessionV2.state !== 'failed' && agentSessionV2.state !== 'cancelled') ||
+        (useAgentMode && !useAgentV2 && agentSession !== null && agentSession.state !== 'complete' && agentSession.state !== 'error' && agentSession.state !== 'cancelled')
       } />
 
+      {/* Model preflight: checking, pulling, warming or failed models */}
+      {pendingModels.length > 0 && (
+        <div className="model-status-list">
+          {pendingModels.map((m) => (
+            <div key={m.model} className={`model-status ${m.state}`}>
+              <span className="model-name">{m.model}</span>
+              <span className="model-state">
+                {m.state === 'pulling' && m.total
+                  ? `pulling ${Math.floor(((m.completed ?? 0) / m.total) * 100)}%`
+                  : m.state}
+              </span>
+              {m.detail && <span className="model-detail">{m.detail}</span>}
+            </div>
+          ))}
+        </div>
+      )}
+
       {/* V2 Plan Progress */}
       {useAgentV2 && agentSessionV2?.plan && (
         <div className="plan-progress">
@@ -200,6 +261,9 @@ export function Overlay() {
           {agentSessionV2.state === 'failed' && agentSessionV2.error && (
             <div className="plan-error">{agentSessionV2.error}</div>
           )}
+          {agentSessionV2.state === 'cancelled' && (
+            <div className="plan-error">Cancelled</div>
+          )}
         </div>
       )}
 
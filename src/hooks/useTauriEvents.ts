@@ -3,27 +3,31 @@ import { listen } from '@tauri-apps/api/event'
 import { invoke } from '@tauri-apps/api/core'
 import { getCurrentWindow, LogicalSize } from '@tauri-apps/api/window'
 import { useOttoStore } from '../store/otto'
-import type { ActionPlan, AgentSession, AgentSessionV2, ScreenState, AtomicAction, ActionResult, VerificationResult, DecompositionInfo, LlmDebugEvent, LlmResponseEvent, ToolResult } from '../types'
+import type { ActionPlan, AgentSession, AgentSessionV2, ScreenState, AtomicAction, ActionResult, VerificationResult, DecompositionInfo, LlmDebugEvent, LlmResponseEvent, LlmTokenEvent, ModelStatusEvent, ToolResult } from '../types'
 
 const INPUT_HEIGHT = 88
 const STEP_HEIGHT = 48
+const MODEL_ROW_HEIGHT = 28
 
 // Calculate and apply window height directly
 async function updateWindowHeight() {
   const storeState = useOttoStore.getState()
-  const { agentSession, agentSessionV2, goalPipelineStates, decompositionInfo, useAgentMode, useAgentV2, state, plan } = storeState
+  const { agentSession, agentSessionV2, goalPipelineStates, decompositionInfo, useAgentMode, useAgentV2, state, plan, modelStatus } = storeState
   const goals = agentSession?.goals || []
   const planSteps = agentSessionV2?.plan?.steps || []
   const legacySteps = plan?.steps || []
 
   let height = INPUT_HEIGHT
 
+  // Models still being checked, pulled or loaded (or that failed)
+  height += Object.values(modelStatus).filter((m) => m.state !== 'ready').length * MODEL_ROW_HEIGHT
+
   // V2 Agent mode (tool-based)
   if (useAgentV2 && agentSessionV2) {
     // Plan steps height
     height += planSteps.length * STEP_HEIGHT
     // Status row
-    if (agentSessionV2.state === 'done' || agentSessionV2.state === 'failed') {
+    if (agentSessionV2.state === 'done' || agentSessionV2.state === 'failed' || agentSessionV2.state === 'cancelled') {
       height += STEP_HEIGHT
     }
     // Extra padding during execution
@@ -68,7 +72,7 @@ async function updateWindowHeight() {
     }
 
     // Done/error row
-    if (agentSession?.state === 'complete' || agentSession?.state === 'error') {
+    if (agentSession?.state === 'complete' || agentSession?.state === 'error' || agentSession?.state === 'cancelled') {
       height += STEP_HEIGHT
     }
   }
@@ -87,7 +91,8 @@ export function useTauriEvents() {
     setPlan, setState, setStepIndex, setError, setDebugLog,
     setAgentSession, updateGoalPipeline, setDecompositionInfo,
     setAgentSessionV2,
-    addLlmPrompt, addLlmResponse
+    addLlmPrompt, addLlmResponse, appendLlmToken,
+    setModelStatus
   } = useOttoStore()
 
   useEffect(() => {
@@ -212,6 +217,8 @@ export function useTauriEvents() {
               setState('done')
             } else if (v2Session.state === 'failed') {
               setState('error')
+            } else if (v2Session.state === 'cancelled') {
+              setState('idle')
             } else if (v2Session.state === 'executing') {
               setState('executing')
             } else if (v2Session.state === 'planning') {
@@ -242,6 +249,8 @@ export function useTauriEvents() {
               setState('done')
             } else if (v1Session.state === 'error') {
               setState('error')
+            } else if (v1Session.state === 'cancelled') {
+              setState('idle')
             }
           }
 
@@ -399,6 +408,15 @@ export function useTauriEvents() {
       )
       unlisteners.push(unlistenLlmPrompt)
 
+      // LLM response streaming in
+      const unlistenLlmToken = await listen<LlmTokenEvent>(
+        'llm_token',
+        (event) => {
+          appendLlmToken(event.payload)
+        }
+      )
+      unlisteners.push(unlistenLlmToken)
+
       // LLM response received
       const unlistenLlmResponse = await listen<LlmResponseEvent>(
         'llm_response',
@@ -408,6 +426,20 @@ export function useTauriEvents() {
       )
       unlisteners.push(unlistenLlmResponse)
 
+      // ============================================
+      // Model Preflight Events
+      // ============================================
+
+      // Model checked, pulling, warming, ready or failed
+      const unlistenModelStatus = await listen<ModelStatusEvent>(
+        'model_status',
+        (event) => {
+          setModelStatus(event.payload)
+          setTimeout(() => updateWindowHeight(), 10)
+        }
+      )
+      unlisteners.push(unlistenModelStatus)
+
       // ============================================
       // V2 Agent Events (Tool-based)
       // ============================================
@@ -458,5 +490,5 @@ export function useTauriEvents() {
       unlisteners.forEach((unlisten) => unlisten())
       unsubscribe()
     }
-  }, [setPlan, setState, setStepIndex, setError, setDebugLog, setAgentSession, updateGoalPipeline, setDecompositionInfo, setAgentSessionV2, addLlmPrompt, addLlmResponse])
+  }, [setPlan, setState, setStepIndex, setError, setDebugLog, setAgentSession, updateGoalPipeline, setDecompositionInfo, setAgentSessionV2, addLlmPrompt, addLlmResponse, appendLlmToken])
 }
@@ -1,5 +1,5 @@
 import { create } from 'zustand'
-import type { OttoStore, AppState, ActionPlan, AgentSession, AgentSessionV2, Goal, GoalPipelineState, DecompositionInfo, LlmDebugEvent, LlmResponseEvent, LlmCallEntry } from '../types'
+import type { OttoStore, AppState, ActionPlan, AgentSession, AgentSessionV2, Goal, GoalPipelineState, DecompositionInfo, LlmDebugEvent, LlmResponseEvent, LlmTokenEvent, LlmCallEntry, ModelStatusEvent } from '../types'
 
 export const useOttoStore = create<OttoStore>((set) => ({
   // Legacy state
@@ -24,6 +24,9 @@ export const useOttoStore = create<OttoStore>((set) => ({
   llmCalls: {},
   selectedLlmCall: null,
 
+  // Model preflight state
+  modelStatus: {},
+
   // Legacy actions
   setCommand: (cmd: string) => set({ command: cmd }),
 
@@ -106,6 +109,9 @@ export const useOttoStore = create<OttoStore>((set) => ({
 
     const updated: LlmCallEntry = {
       ...existing,
+      model: event.model ?? existing.model,
+      attempts: event.attempts,
+      usage: event.usage,
       raw_response: event.raw_response,
       parsed_result: event.parsed_result,
       duration_ms: event.duration_ms,
@@ -118,7 +124,26 @@ export const useOttoStore = create<OttoStore>((set) => ({
     }
   }),
 
+  appendLlmToken: (event: LlmTokenEvent) => set((state) => {
+    const existing = state.llmCalls[event.call_id]
+    // Ignore late tokens once the final response has arrived
+    if (!existing || existing.status !== 'pending') return state
+
+    const updated: LlmCallEntry = {
+      ...existing,
+      // A retry restarts the stream at index 0
+      raw_response: (event.index === 0 ? '' : existing.raw_response ?? '') + event.token
+    }
+    return {
+      llmCalls: { ...state.llmCalls, [event.call_id]: updated }
+    }
+  }),
+
   selectLlmCall: (callId: string | null) => set({ selectedLlmCall: callId }),
 
   clearLlmCalls: () => set({ llmCalls: {}, selectedLlmCall: null }),
+
+  setModelStatus: (event: ModelStatusEvent) => set((state) => ({
+    modelStatus: { ...state.modelStatus, [event.model]: event }
+  })),
 }))
@@ -7,6 +7,7 @@ export type ActionType =
   | 'mouse_move'
   | 'wait'
   | 'find_and_click'
+  | 'find_image_and_click'
 
 // Type-specific parameters
 export type ActionParams =
@@ -17,6 +18,7 @@ export type ActionParams =
   | { x: number; y: number }                          // mouse_move
   | { ms: number }                                    // wait
   | { element: string }                               // find_and_click
+  | { image_path: string }                            // find_image_and_click
 
 // Individual action step
 export interface ActionStep {
@@ -50,6 +52,7 @@ export type AgentState =
   | 'verifying'
   | 'complete'
   | 'error'
+  | 'cancelled'
 
 export type GoalStatus = 'pending' | 'in_progress' | 'completed' | 'failed'
 
@@ -91,7 +94,7 @@ export interface ScreenState {
   description: string
   detected_elements: DetectedElement[]
   active_app?: string
-  screenshot_hash: string
+  screenshot_hash: string  // perceptual hash, hex
 }
 
 export interface DetectedElement {
@@ -111,7 +114,7 @@ export interface ActionResult {
   action_id: string
   success: boolean
   error_message?: string
-  screen_changed: boolean
+  screen_changed: boolean  // measured by diffing captures before and after
 }
 
 export interface VerificationResult {
@@ -134,6 +137,7 @@ export interface AgentSession {
   current_action?: AtomicAction
   last_observation?: ScreenState
   error?: string
+  usage: SessionUsage
 }
 
 // ============================================
@@ -158,10 +162,70 @@ export interface LlmDebugEvent {
 export interface LlmResponseEvent {
   call_id: string
   raw_response: string
-  parsed_result?: string
+  parsed_result?: string  // JSON calls: the JSON parsed, after a "Repaired: ..." line if it was fixed
   duration_ms: number
   success: boolean
   error?: string
+  model?: string  // set when a fallback model answered
+  attempts?: LlmAttempt[]
+  usage?: LlmUsage
+}
+
+// Token counts and server-side timings for one call
+export interface LlmUsage {
+  prompt_tokens: number
+  completion_tokens: number
+  total_duration_ms: number
+  load_duration_ms: number
+}
+
+export interface CallTypeUsage {
+  calls: number
+  prompt_tokens: number
+  completion_tokens: number
+  load_duration_ms: number
+  duration_ms: number
+}
+
+// LLM usage accumulated over an agent session
+export interface SessionUsage extends CallTypeUsage {
+  by_call_type: Partial<Record<LlmCallType, CallTypeUsage>>
+}
+
+// Parse failures fixed by reprompting, per prompt (get_repair_stats)
+export interface RepairStats {
+  calls: number
+  parse_failures: number
+  repaired: number
+  repair_attempts: number
+}
+
+// One try of an LLM call (retries and fallbacks included)
+export interface LlmAttempt {
+  model: string
+  fallback: boolean
+  error?: string
+  duration_ms: number
+  backoff_ms: number
+}
+
+// Startup check of one model (check, pull, warm up)
+export type ModelState = 'checking' | 'pulling' | 'warming' | 'ready' | 'failed'
+
+export interface ModelStatusEvent {
+  model: string
+  server: string
+  state: ModelState
+  detail?: string  // pull status line, load time or error
+  completed?: number  // bytes downloaded while pulling
+  total?: number
+}
+
+// Streamed chunk of an in-flight LLM call
+export interface LlmTokenEvent {
+  call_id: string
+  token: string
+  index: number
 }
 
 export interface LlmCallEntry {
@@ -176,6 +240,8 @@ export interface LlmCallEntry {
   duration_ms?: number
   success?: boolean
   error?: string
+  attempts?: LlmAttempt[]
+  usage?: LlmUsage
   status: 'pending' | 'success' | 'error'
 }
 
@@ -183,7 +249,7 @@ export interface LlmCallEntry {
 // New Tool-based Agent Types (v2)
 // ============================================
 
-export type AgentStateV2 = 'idle' | 'planning' | 'executing' | 'done' | 'failed'
+export type AgentStateV2 = 'idle' | 'planning' | 'executing' | 'done' | 'failed' | 'cancelled'
 
 export type StepStatus = 'pending' | 'in_progress' | 'done' | 'failed'
 
@@ -201,15 +267,18 @@ export interface Plan {
 
 export interface UIElement {
   label: string
-  element_type: string
+  element_type: string  // button, text_field, menu, icon, link, tab, checkbox, text, or as detected
   x: number
   y: number
 }
 
 export interface ToolOutput {
-  type: 'screenshot' | 'ack'
+  type: 'screenshot' | 'image_match' | 'ack'
   elements?: UIElement[]
   active_app?: string
+  x?: number  // image_match: where the reference image was clicked
+  y?: number
+  confidence?: number
 }
 
 export interface ToolResult {
@@ -226,6 +295,74 @@ export interface AgentSessionV2 {
   plan: Plan | null
   step_count: number
   error?: string
+  usage: SessionUsage
+}
+
+// ============================================
+// Settings (get_settings / update_settings / reset_settings)
+// ============================================
+
+export interface Settings {
+  version: number
+  llm: {
+    backend: string  // "ollama" | "openai" | "embedded"
+    base_url: string  // empty = backend default
+    api_key: string | null
+    model: string
+    timeout_secs: number
+    routes: Partial<Record<LlmCallType, CallRoute>>  // text calls
+  }
+  vision: {
+    base_url: string
+    model: string
+    timeout_secs: number
+    replay_dir: string  // empty = the model; otherwise a recorded screen and answers to replay
+    set_of_mark: boolean  // pick click targets by numbered boxes drawn on the screenshot
+    refine_size: number  // points around a located element re-asked at full resolution; 0 = off
+    tile_grid: number  // also detect on N x N full-resolution tiles; 0 or 1 = off
+    tile_concurrency: number  // tiles sent to the vision model at once
+    routes: Partial<Record<LlmCallType, CallRoute>>  // vision calls
+  }
+  limits: {
+    max_steps: number
+    max_total_actions: number
+    max_goal_attempts: number
+    max_repairs: number  // reprompts when an answer doesn't parse
+  }
+  retry: {
+    max_retries: number
+    initial_backoff_ms: number
+    max_backoff_ms: number
+    breaker_threshold: number
+    breaker_cooldown_secs: number
+  }
+  preflight: {
+    enabled: boolean
+    auto_pull: boolean
+    warm_up: boolean
+    keep_alive: string  // e.g. "30m", "-1" keeps models loaded forever
+  }
+  embedded: {
+    model_path: string  // empty = the model fetched by scripts/download-model.sh
+    fallback: boolean  // use it for text calls when Ollama isn't running
+  }
+  ocr: {
+    enabled: boolean  // add text boxes found by OCR to the detected elements when the models are found
+    model_dir: string  // empty = the models fetched by scripts/download-ocr-models.sh
+  }
+}
+
+// Model and generation options for one LLM call type
+export interface CallRoute {
+  model: string  // empty = the section's model (llm.model or vision.model)
+  options: {
+    temperature?: number
+    seed?: number
+    num_ctx?: number
+    num_predict?: number
+    stop?: string[]
+  }
+  fallback_models: string[]  // tried in order when the model keeps failing
 }
 
 // ============================================
@@ -255,6 +392,9 @@ export interface OttoStore {
   llmCalls: Record<string, LlmCallEntry>  // call_id -> entry
   selectedLlmCall: string | null
 
+  // Model preflight state
+  modelStatus: Record<string, ModelStatusEvent>  // model -> latest status
+
   // Legacy actions
   setCommand: (cmd: string) => void
   setPlan: (plan: ActionPlan) => void
@@ -278,6 +418,10 @@ export interface OttoStore {
   // Debug actions
   addLlmPrompt: (event: LlmDebugEvent) => void
   addLlmResponse: (event: LlmResponseEvent) => void
+  appendLlmToken: (event: LlmTokenEvent) => void
   selectLlmCall: (callId: string | null) => void
   clearLlmCalls: () => void
+
+  // Preflight actions
+  setModelStatus: (event: ModelStatusEvent) => void
 }